@@ -0,0 +1,48 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rust_project::config::Valuations;
+use rust_project::instance::create_groups_pref;
+use rust_project::solver::{AuctionSolver, KuhnMunkresSolver, MatchingSolver, SuccessiveShortestPathSolver};
+
+// The first group of a random instance against every item, the largest matching the
+// round robin solves.
+fn bench_solvers(c: &mut Criterion) {
+    let mut group = c.benchmark_group("group_against_items");
+    for &(n_each, num_items) in &[(5, 20), (10, 100), (20, 200)] {
+        let (groups, preferences) = create_groups_pref(n_each, 2, 2 * n_each, num_items, Valuations::Goods);
+        let weights: Vec<Vec<f64>> = groups[0].iter().map(|&agent| preferences[agent].clone()).collect();
+        let size = format!("{}x{}", n_each, num_items);
+
+        group.bench_with_input(BenchmarkId::new("ssp", &size), &weights, |b, weights| {
+            b.iter(|| SuccessiveShortestPathSolver.solve(weights, n_each).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("auction", &size), &weights, |b, weights| {
+            b.iter(|| AuctionSolver.solve(weights, n_each).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("km", &size), &weights, |b, weights| {
+            b.iter(|| KuhnMunkresSolver.solve(weights, n_each).unwrap())
+        });
+    }
+    group.finish();
+}
+
+// A few agents against many items, where only the agents bid in the auction; the
+// padded Kuhn-Munkres would solve an `m`-square matrix, so it is left out.
+fn bench_few_rows(c: &mut Criterion) {
+    let mut group = c.benchmark_group("few_agents_against_many_items");
+    for &(n_each, num_items) in &[(5, 2000), (10, 5000)] {
+        let (groups, preferences) = create_groups_pref(n_each, 1, n_each, num_items, Valuations::Goods);
+        let weights: Vec<Vec<f64>> = groups[0].iter().map(|&agent| preferences[agent].clone()).collect();
+        let size = format!("{}x{}", n_each, num_items);
+
+        group.bench_with_input(BenchmarkId::new("ssp", &size), &weights, |b, weights| {
+            b.iter(|| SuccessiveShortestPathSolver.solve(weights, n_each).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("auction", &size), &weights, |b, weights| {
+            b.iter(|| AuctionSolver.solve(weights, n_each).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_solvers, bench_few_rows);
+criterion_main!(benches);
@@ -1,86 +1,654 @@
-use crate::matching::compute_max_weight_matching;
+use crate::binary::{bundle_matching, BinaryMatching};
+use crate::error::MatchingError;
+use crate::matching::{compute_max_weight_assignment_with, compute_max_weight_capacitated_matching_with, compute_max_weight_matching_with, Assignment, IncrementalMatching, MultiAssignment};
+use crate::picking::PickingSequence;
+use crate::preferences::Preferences;
+use crate::solver::{MatchingSolver, SuccessiveShortestPathSolver};
+use crate::tie_break::TieBreak;
+use crate::weight::Weight;
 use std::collections::HashSet;
+use std::collections::VecDeque;
 use std::collections::HashMap;
 
 
-pub fn round_robin_allocation_by_group(num_items: usize, num_groups: usize, n_each: usize, groups: &Vec<Vec<usize>>, preferences: &Vec<Vec<f64>>) -> (Vec<HashSet<usize>>, Vec<f64>) {
+/// Bundle, utility and per-member assignment of every group.
+pub type RoundRobinOutcome<W> = (Vec<HashSet<usize>>, Vec<W>, Vec<Assignment<W>>);
+
+/// Same as `RoundRobinOutcome`, with every bundle given as the number of units the
+/// group holds of each of its items.
+pub type UnitsOutcome<W> = (Vec<HashMap<usize, usize>>, Vec<W>, Vec<Assignment<W>>);
+
+/// Same as `UnitsOutcome`, with every member possibly holding several items.
+pub type CapacitatedOutcome<W> = (Vec<HashMap<usize, usize>>, Vec<W>, Vec<MultiAssignment<W>>);
+
+pub fn round_robin_allocation_by_group<W: Weight, P: Preferences<W> + ?Sized>(num_items: usize, num_groups: usize, n_each: usize, groups: &[Vec<usize>], preferences: &P) -> Result<RoundRobinOutcome<W>, MatchingError> {
+    round_robin_allocation_by_group_with(&SuccessiveShortestPathSolver, num_items, num_groups, n_each, groups, preferences)
+}
+
+/// `round_robin_allocation_by_group` with the group matchings found by the given solver.
+///
+/// Preferences may have any sign. Every item a group takes is handled by one of its
+/// members, so a group that only has chores left still takes one on its turn, the one
+/// that lowers its utility the least, and its members stay unmatched only while its
+/// bundle is smaller than the group.
+///
+/// Fails when fewer than `num_groups` groups are given or when `preferences` does not
+/// cover every member and item. See `round_robin_allocation_capacitated` for members
+/// that may use several items.
+pub fn round_robin_allocation_by_group_with<W: Weight, P: Preferences<W> + ?Sized, S: MatchingSolver<W>>(solver: &S, num_items: usize, num_groups: usize, n_each: usize, groups: &[Vec<usize>], preferences: &P) -> Result<RoundRobinOutcome<W>, MatchingError> {
+    round_robin_allocation_by_group_with_tie_break(solver, &TieBreak::Solver, num_items, num_groups, n_each, groups, preferences)
+}
+
+/// `round_robin_allocation_by_group_with` where a group that has several items of
+/// the same value to pick from takes the one `tie_break` prefers, so the allocation
+/// does not depend on the solver.
+#[allow(clippy::too_many_arguments)]
+pub fn round_robin_allocation_by_group_with_tie_break<W: Weight, P: Preferences<W> + ?Sized, S: MatchingSolver<W>>(solver: &S, tie_break: &TieBreak, num_items: usize, num_groups: usize, n_each: usize, groups: &[Vec<usize>], preferences: &P) -> Result<RoundRobinOutcome<W>, MatchingError> {
+    round_robin_allocation_by_group_with_sequence(solver, tie_break, &PickingSequence::RoundRobin, num_items, num_groups, n_each, groups, preferences)
+}
+
+/// `round_robin_allocation_by_group_with_tie_break` where the groups pick in the
+/// order of `sequence` instead of `0..num_groups` every round.
+///
+/// Round robin stops once every group holds `n_each` items, no item is left, or a
+/// whole round passes without a pick, e.g. when the groups the sequence names are
+/// full. Fails when the sequence is empty or names a group beyond `num_groups`.
+#[allow(clippy::too_many_arguments)]
+pub fn round_robin_allocation_by_group_with_sequence<W: Weight, P: Preferences<W> + ?Sized, S: MatchingSolver<W>>(solver: &S, tie_break: &TieBreak, sequence: &PickingSequence, num_items: usize, num_groups: usize, n_each: usize, groups: &[Vec<usize>], preferences: &P) -> Result<RoundRobinOutcome<W>, MatchingError> {
+    round_robin_allocation_with_quotas(solver, tie_break, sequence, num_items, &vec![n_each; num_groups], groups, preferences)
+}
+
+/// `round_robin_allocation_by_group_with_sequence` for groups of any sizes, where
+/// group `p` takes up to `quotas[p]` items instead of `n_each`; there are
+/// `quotas.len()` groups.
+///
+/// A quota beyond the size of its group still lets the group take items, which its
+/// members cannot all use.
+pub fn round_robin_allocation_with_quotas<W: Weight, P: Preferences<W> + ?Sized, S: MatchingSolver<W>>(solver: &S, tie_break: &TieBreak, sequence: &PickingSequence, num_items: usize, quotas: &[usize], groups: &[Vec<usize>], preferences: &P) -> Result<RoundRobinOutcome<W>, MatchingError> {
+    let (allocation, utility_list, assignment_list) = round_robin_allocation_of_units_tie_broken(solver, tie_break, sequence, num_items, &vec![1; num_items], quotas, groups, preferences)?;
+    let allocation = allocation.into_iter().map(|bundle| bundle.into_keys().collect()).collect();
+    Ok((allocation, utility_list, assignment_list))
+}
+
+/// `round_robin_allocation_with_quotas` where group `p` picks in proportion to
+/// `entitlements[p]`, by the weighted picking sequence, e.g. the size of the group.
+///
+/// With additive valuations, such as those of groups whose members each take one
+/// item of a bundle that fits the group, the allocation is weighted EF1; see
+/// `is_weighted_ef1`. Fails when an entitlement is missing or not positive.
+#[allow(clippy::too_many_arguments)]
+pub fn weighted_round_robin_allocation<W: Weight, P: Preferences<W> + ?Sized, S: MatchingSolver<W>>(solver: &S, tie_break: &TieBreak, num_items: usize, quotas: &[usize], entitlements: &[W], groups: &[Vec<usize>], preferences: &P) -> Result<RoundRobinOutcome<W>, MatchingError> {
+    let sequence = PickingSequence::Weighted { entitlements: entitlements.iter().map(|&entitlement| entitlement.as_f64()).collect(), shift: 0.0 };
+    round_robin_allocation_with_quotas(solver, tie_break, &sequence, num_items, quotas, groups, preferences)
+}
+
+pub fn is_weighted_ef1<W: Weight, P: Preferences<W> + ?Sized>(allocation: &[HashSet<usize>], entitlements: &[W], groups: &[Vec<usize>], preferences: &P) -> Result<bool, MatchingError> {
+    is_weighted_ef1_with(&SuccessiveShortestPathSolver, allocation, entitlements, groups, preferences)
+}
+
+/// Whether every group `p` values its bundle, relative to its entitlement, at least
+/// as much as the bundle of any group `q` relative to the entitlement of `q`, once
+/// one item is removed from either bundle:
+/// `entitlements[q] * v_p(A_p) >= entitlements[p] * v_p(A_q)` up to that item.
+/// Removing the item from `A_q` covers goods, removing it from `A_p` chores.
+///
+/// A group values a bundle at its maximum weight matching of
+/// `min(group size, bundle size)` pairs, as the experiments do. Equal entitlements
+/// make this EF1. Fails when a group or an entitlement of the allocation is missing.
+pub fn is_weighted_ef1_with<W: Weight, P: Preferences<W> + ?Sized, S: MatchingSolver<W>>(solver: &S, allocation: &[HashSet<usize>], entitlements: &[W], groups: &[Vec<usize>], preferences: &P) -> Result<bool, MatchingError> {
+    let num_groups = allocation.len();
+    if groups.len() < num_groups {
+        return Err(MatchingError::MissingGroups { num_groups, found: groups.len() });
+    }
+    if entitlements.len() < num_groups {
+        return Err(MatchingError::MissingEntitlements { num_groups, found: entitlements.len() });
+    }
+    let value = |p: usize, bundle: &[usize]| bundle_value(solver, &groups[p], bundle, preferences);
+    let without = |bundle: &[usize], item: usize| bundle.iter().copied().filter(|&x| x != item).collect::<Vec<usize>>();
+
+    for p in 0..num_groups {
+        let own: Vec<usize> = allocation[p].iter().copied().collect();
+        let own_value = value(p, &own)?;
+        for q in (0..num_groups).filter(|&q| q != p) {
+            let other: Vec<usize> = allocation[q].iter().copied().collect();
+            let other_value = value(p, &other)?;
+            let holds = |own_value: W, other_value: W| !(entitlements[q] * own_value).definitely_lt(entitlements[p] * other_value);
+            if holds(own_value, other_value) {
+                continue;
+            }
+            let mut found = false;
+            for &item in &other {
+                found = found || holds(own_value, value(p, &without(&other, item))?);
+            }
+            for &item in &own {
+                found = found || holds(value(p, &without(&own, item))?, other_value);
+            }
+            if !found {
+                return Ok(false);
+            }
+        }
+    }
+    Ok(true)
+}
+
+/// Round robin over items with several identical units, `units[item]` of each, e.g.
+/// the seats of a course.
+///
+/// A group takes one unit on its turn and may take several units of the same item,
+/// which then go to different members.
+pub fn round_robin_allocation_of_units<W: Weight, P: Preferences<W> + ?Sized>(num_items: usize, units: &[usize], num_groups: usize, n_each: usize, groups: &[Vec<usize>], preferences: &P) -> Result<UnitsOutcome<W>, MatchingError> {
+    round_robin_allocation_of_units_with(&SuccessiveShortestPathSolver, num_items, units, num_groups, n_each, groups, preferences)
+}
+
+/// `round_robin_allocation_of_units` with the group matchings found by the given solver.
+pub fn round_robin_allocation_of_units_with<W: Weight, P: Preferences<W> + ?Sized, S: MatchingSolver<W>>(solver: &S, num_items: usize, units: &[usize], num_groups: usize, n_each: usize, groups: &[Vec<usize>], preferences: &P) -> Result<UnitsOutcome<W>, MatchingError> {
+    round_robin_allocation_of_units_tie_broken(solver, &TieBreak::Solver, &PickingSequence::RoundRobin, num_items, units, &vec![n_each; num_groups], groups, preferences)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn round_robin_allocation_of_units_tie_broken<W: Weight, P: Preferences<W> + ?Sized, S: MatchingSolver<W>>(solver: &S, tie_break: &TieBreak, sequence: &PickingSequence, num_items: usize, units: &[usize], quotas: &[usize], groups: &[Vec<usize>], preferences: &P) -> Result<UnitsOutcome<W>, MatchingError> {
+    let num_agents = groups.iter().take(quotas.len()).flatten().max().map_or(0, |&agent| agent + 1);
+    let (allocation, utility_list, assignment_list) = round_robin_allocation_capacitated_tie_broken(solver, tie_break, sequence, num_items, units, &vec![1; num_agents], quotas, groups, preferences)?;
+    // Every member holds at most one item.
+    let assignment_list = assignment_list
+        .into_iter()
+        .map(|assignment| assignment.into_iter().filter_map(|(agent, items)| items.first().map(|&item| (agent, item))).collect())
+        .collect();
+    Ok((allocation, utility_list, assignment_list))
+}
+
+/// Round robin in which agent `agent` may use up to `capacities[agent]` different
+/// items, so a group gets value from up to `n_each` units even when it has fewer
+/// members.
+#[allow(clippy::too_many_arguments)]
+pub fn round_robin_allocation_capacitated<W: Weight, P: Preferences<W> + ?Sized>(num_items: usize, units: &[usize], capacities: &[usize], num_groups: usize, n_each: usize, groups: &[Vec<usize>], preferences: &P) -> Result<CapacitatedOutcome<W>, MatchingError> {
+    round_robin_allocation_capacitated_with(&SuccessiveShortestPathSolver, num_items, units, capacities, num_groups, n_each, groups, preferences)
+}
+
+/// `round_robin_allocation_capacitated` with the group matchings found by the given solver.
+#[allow(clippy::too_many_arguments)]
+pub fn round_robin_allocation_capacitated_with<W: Weight, P: Preferences<W> + ?Sized, S: MatchingSolver<W>>(solver: &S, num_items: usize, units: &[usize], capacities: &[usize], num_groups: usize, n_each: usize, groups: &[Vec<usize>], preferences: &P) -> Result<CapacitatedOutcome<W>, MatchingError> {
+    round_robin_allocation_capacitated_tie_broken(solver, &TieBreak::Solver, &PickingSequence::RoundRobin, num_items, units, capacities, &vec![n_each; num_groups], groups, preferences)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn round_robin_allocation_capacitated_tie_broken<W: Weight, P: Preferences<W> + ?Sized, S: MatchingSolver<W>>(solver: &S, tie_break: &TieBreak, sequence: &PickingSequence, num_items: usize, units: &[usize], capacities: &[usize], quotas: &[usize], groups: &[Vec<usize>], preferences: &P) -> Result<CapacitatedOutcome<W>, MatchingError> {
+    let num_groups = quotas.len();
+    if groups.len() < num_groups {
+        return Err(MatchingError::MissingGroups { num_groups, found: groups.len() });
+    }
+    if units.len() < num_items {
+        return Err(MatchingError::MissingUnits { item: units.len(), num_items });
+    }
+    sequence.validate(num_groups)?;
     // let mut allocation: HashMap<usize, Vec<usize>> = HashMap::new();
-    let mut allocation: Vec<HashSet<usize>> = vec![HashSet::new(); num_groups];
+    let mut allocation: Vec<HashMap<usize, usize>> = vec![HashMap::new(); num_groups];
     // println!("allocation {:?}",allocation);
 
-    let mut utility_list: Vec<f64> = vec![0.0; num_groups];
+    let mut utility_list: Vec<W> = vec![W::zero(); num_groups];
 
-    let mut available_items: Vec<usize> = (0..num_items).collect();
+    let mut remaining: Vec<usize> = units[..num_items].to_vec();
+    let mut available_items: Vec<usize> = (0..num_items).filter(|&item| remaining[item] > 0).collect();
 
     let mut selected: HashMap<usize, bool> = HashMap::new();
     for p in 0..num_groups {
         selected.insert(p, false);
     }
 
-    let mut match_size = 0;
+    let mut matchings: Vec<GroupMatching<W>> = groups
+        .iter()
+        .take(num_groups)
+        .map(|group| GroupMatching::new(solver, tie_break, group.clone(), capacities, &remaining, preferences))
+        .collect::<Result<_, _>>()?;
 
-    while !available_items.is_empty() {
+    let bundle_size = |bundle: &HashMap<usize, usize>| bundle.values().sum::<usize>();
 
-        match_size += 1;
+    let mut rounds = sequence.rounds(num_groups);
+    while !available_items.is_empty() {
 
         // println!("---allocation {:?}", allocation);
         // println!("---available_items {:?}", available_items);
-        // println!("---round {:?}", match_size);
 
-        let mut terminate = true;
-        for p in 0..num_groups {
-            if allocation[p].len() < n_each {
-                terminate = false;
+        if allocation.iter().zip(quotas).all(|(bundle, &quota)| bundle_size(bundle) >= quota) {
+            break;
+        }
+
+        // The groups the sequence names may all be full.
+        let mut picked = false;
+        for p in rounds.next().unwrap_or_default() {
+
+            // println!("---Class {:?}", p);
+
+            if bundle_size(&allocation[p]) >= quotas[p] || available_items.is_empty() {
+                continue;
+            }
+
+            // With sparse preferences a group may have no edge left to grow its matching
+            // by; it still takes its turn, with an item it cannot use.
+            let item = match matchings[p].pick(solver, &allocation[p], &remaining, preferences)? {
+                Some(item) => item,
+                None => *available_items.iter().min_by_key(|&&item| tie_break.key(item)).unwrap_or(&available_items[0]),
+            };
+
+            // println!("item {:?}", item);
+
+            if remaining.get(item).is_none_or(|&left| left == 0) {
+                return Err(MatchingError::BrokenInvariant(format!("group {} picked item {}, which is not available", p, item)));
+            }
+            remaining[item] -= 1;
+            if remaining[item] == 0 {
+                available_items.retain(|&x| x != item);
+            }
+            *allocation[p].entry(item).or_insert(0) += 1;
+            utility_list[p] = matchings[p].weight();
+            picked = true;
+
+            for (q, matching) in matchings.iter_mut().enumerate() {
+                if q != p {
+                    matching.remove_item(item);
+                }
             }
         }
-        if terminate {
+        if !picked {
             break;
         }
+    }
+    let assignment_list: Vec<MultiAssignment<W>> = matchings.iter().map(|matching| matching.assignment()).collect();
+    Ok((allocation, utility_list, assignment_list))
+}
 
+/// Yankee Swap allocation of the items to the groups when every preference is zero
+/// or one, with the inputs and outcome of `round_robin_allocation_by_group`.
+///
+/// A group values a bundle at the size of a maximum matching of its members to items
+/// they value, a matroid rank function, for which Yankee Swap gives a leximin, MNW
+/// and EF1 allocation. The group of the smallest utility, the first on ties, takes an
+/// item it values, from the pool or from another group that takes a replacement of
+/// the same value in turn, along a shortest chain of such swaps ending in the pool.
+/// A group that finds no chain, or holds `n_each` items, stops taking. Every member
+/// values the item matched to it, so a group's utility is the number of items it
+/// holds, and the items no group can use stay unallocated.
+///
+/// Fails when a preference is neither zero nor one, when fewer than `num_groups`
+/// groups are given or when `preferences` does not cover every member and item.
+pub fn yankee_swap_allocation<W: Weight, P: Preferences<W> + ?Sized>(num_items: usize, num_groups: usize, n_each: usize, groups: &[Vec<usize>], preferences: &P) -> Result<RoundRobinOutcome<W>, MatchingError> {
+    yankee_swap_allocation_with_quotas(num_items, &vec![n_each; num_groups], groups, preferences)
+}
 
-        for p in 0..num_groups {
+/// `yankee_swap_allocation` for groups of any sizes, where group `p` stops taking
+/// once it holds `quotas[p]` items; there are `quotas.len()` groups.
+pub fn yankee_swap_allocation_with_quotas<W: Weight, P: Preferences<W> + ?Sized>(num_items: usize, quotas: &[usize], groups: &[Vec<usize>], preferences: &P) -> Result<RoundRobinOutcome<W>, MatchingError> {
+    let num_groups = quotas.len();
+    if groups.len() < num_groups {
+        return Err(MatchingError::MissingGroups { num_groups, found: groups.len() });
+    }
+    let items: Vec<usize> = (0..num_items).collect();
+    let adjacency: Vec<Vec<Vec<usize>>> = groups.iter().take(num_groups).map(|group| binary_adjacency(group, &items, preferences)).collect::<Result<_, _>>()?;
 
-            // println!("---Class {:?}", p);
+    let mut owner: Vec<Option<usize>> = vec![None; num_items];
+    let mut bundles: Vec<Vec<usize>> = vec![Vec::new(); num_groups];
+    let mut active = vec![true; num_groups];
+    while let Some(p) = (0..num_groups).filter(|&p| active[p]).min_by_key(|&p| bundles[p].len()) {
+        if bundles[p].len() >= quotas[p] {
+            active[p] = false;
+            continue;
+        }
+        let Some(path) = swap_path(p, &adjacency, &bundles, &owner) else {
+            active[p] = false;
+            continue;
+        };
+        // Each group along the path gives its item to the one before and takes the
+        // next item instead, the last one from the pool.
+        let mut taker = p;
+        for &item in &path {
+            let giver = owner[item];
+            if let Some(q) = giver {
+                bundles[q].retain(|&x| x != item);
+            }
+            bundles[taker].push(item);
+            owner[item] = Some(taker);
+            match giver {
+                Some(q) => taker = q,
+                None => break,
+            }
+        }
+    }
+
+    let mut allocation: Vec<HashSet<usize>> = Vec::with_capacity(num_groups);
+    let mut utility_list: Vec<W> = Vec::with_capacity(num_groups);
+    let mut assignment_list: Vec<Assignment<W>> = Vec::with_capacity(num_groups);
+    for (p, bundle) in bundles.iter().enumerate() {
+        let matched = bundle_matching(&adjacency[p], bundle);
+        if matched.len() < bundle.len() {
+            return Err(MatchingError::BrokenInvariant(format!("group {} holds an item none of its members uses", p)));
+        }
+        allocation.push(bundle.iter().copied().collect());
+        utility_list.push(matched.iter().fold(W::zero(), |acc, _| acc + W::one()));
+        assignment_list.push(matched.into_iter().map(|(i, item)| (groups[p][i], (item, W::one()))).collect());
+    }
+    Ok((allocation, utility_list, assignment_list))
+}
+
+// Items each member of the group values at one; items that are not an edge are not valued
+fn binary_adjacency<W: Weight, P: Preferences<W> + ?Sized>(group: &[usize], items: &[usize], preferences: &P) -> Result<Vec<Vec<usize>>, MatchingError> {
+    preferences.validate(group, items)?;
+    group
+        .iter()
+        .map(|&agent| {
+            let mut valued = Vec::new();
+            for &item in items {
+                match preferences.value(agent, item) {
+                    Some(value) if value.is_one() => valued.push(item),
+                    Some(value) if !value.is_zero() => return Err(MatchingError::NotBinary { agent, item }),
+                    _ => {}
+                }
+            }
+            Ok(valued)
+        })
+        .collect()
+}
+
+// Shortest chain of items for group p in the exchange graph of Yankee Swap: it starts at an item p
+// values on top of its bundle, every item is followed by one its owner may hold instead at the same
+// value, and it ends in the pool. p itself may pass on an item and take another one on the way.
+fn swap_path(p: usize, adjacency: &[Vec<Vec<usize>>], bundles: &[Vec<usize>], owner: &[Option<usize>]) -> Option<Vec<usize>> {
+    let num_items = owner.len();
+    let independent = |q: usize, bundle: &[usize]| bundle_matching(&adjacency[q], bundle).len() == bundle.len();
+    let mut previous: Vec<Option<usize>> = vec![None; num_items];
+    let mut visited = vec![false; num_items];
+    let mut queue = VecDeque::new();
+    let mut larger = bundles[p].clone();
+    for item in (0..num_items).filter(|&item| owner[item] != Some(p)) {
+        larger.push(item);
+        if independent(p, &larger) {
+            visited[item] = true;
+            queue.push_back(item);
+        }
+        larger.pop();
+    }
 
-            if allocation[p].len() == n_each {
+    while let Some(item) = queue.pop_front() {
+        let Some(q) = owner[item] else {
+            let mut path = vec![item];
+            while let Some(before) = previous[path[path.len() - 1]] {
+                path.push(before);
+            }
+            path.reverse();
+            return Some(path);
+        };
+        for next in (0..num_items).filter(|&next| owner[next] != Some(q)) {
+            if visited[next] {
                 continue;
             }
+            let swapped: Vec<usize> = bundles[q].iter().map(|&x| if x == item { next } else { x }).collect();
+            if independent(q, &swapped) {
+                visited[next] = true;
+                previous[next] = Some(item);
+                queue.push_back(next);
+            }
+        }
+    }
+    None
+}
+
+/// Value of `bundle` to `group`: its maximum weight matching of
+/// `min(group size, bundle size)` pairs.
+pub(crate) fn bundle_value<W: Weight, P: Preferences<W> + ?Sized, S: MatchingSolver<W>>(solver: &S, group: &[usize], bundle: &[usize], preferences: &P) -> Result<W, MatchingError> {
+    let size = group.len().min(bundle.len());
+    Ok(compute_max_weight_matching_with(solver, group.to_vec(), bundle.to_vec(), preferences, size)?.0)
+}
 
-            let mut aval_items_for_each_group = available_items.clone();
-            if !&allocation[p].is_empty() {
-                aval_items_for_each_group.extend(&allocation[p]);
+pub fn envy_cycle_allocation<W: Weight, P: Preferences<W> + ?Sized>(num_items: usize, num_groups: usize, n_each: usize, groups: &[Vec<usize>], preferences: &P) -> Result<RoundRobinOutcome<W>, MatchingError> {
+    envy_cycle_allocation_with_quotas(&SuccessiveShortestPathSolver, num_items, &vec![n_each; num_groups], groups, preferences)
+}
+
+/// Envy-cycle elimination (Lipton et al.) with the inputs and outcome of
+/// `round_robin_allocation_with_quotas`: the items, in increasing order, each go to
+/// the first group nobody envies. When every group is envied, the envy graph has a
+/// cycle, and every group on it takes the bundle of the group it envies, which
+/// removes the cycle without adding envy. A group values a bundle at its maximum
+/// weight matching of `min(group size, bundle size)` pairs.
+///
+/// For goods the allocation is EF1, as long as the quotas do not bind: a group only
+/// takes a bundle within its quota, and when the groups nobody envies are full, the
+/// item goes to the first group below its quota. Allocation stops once every group
+/// is full. Fails when fewer than `quotas.len()` groups are given or when
+/// `preferences` does not cover every member and item.
+pub fn envy_cycle_allocation_with_quotas<W: Weight, P: Preferences<W> + ?Sized, S: MatchingSolver<W>>(solver: &S, num_items: usize, quotas: &[usize], groups: &[Vec<usize>], preferences: &P) -> Result<RoundRobinOutcome<W>, MatchingError> {
+    let num_groups = quotas.len();
+    if groups.len() < num_groups {
+        return Err(MatchingError::MissingGroups { num_groups, found: groups.len() });
+    }
+    let mut bundles: Vec<Vec<usize>> = vec![Vec::new(); num_groups];
+    // values[p][q]: the value of the bundle of q to p.
+    let mut values: Vec<Vec<W>> = vec![vec![W::zero(); num_groups]; num_groups];
+    let envies = |values: &[Vec<W>], p: usize, q: usize| p != q && values[p][p].definitely_lt(values[p][q]);
+
+    for item in 0..num_items {
+        let open: Vec<usize> = (0..num_groups).filter(|&p| bundles[p].len() < quotas[p]).collect();
+        let Some(&first_open) = open.first() else {
+            break;
+        };
+        let receiver = loop {
+            if let Some(&r) = open.iter().find(|&&r| (0..num_groups).all(|p| !envies(&values, p, r))) {
+                break r;
+            }
+            // Follow enviers back from a group below its quota until a group repeats.
+            let mut path = vec![first_open];
+            let cycle_start = loop {
+                let current = *path.last().unwrap_or(&first_open);
+                let envier = (0..num_groups).find(|&p| envies(&values, p, current) && bundles[current].len() <= quotas[p]);
+                match envier {
+                    Some(p) => match path.iter().position(|&q| q == p) {
+                        Some(start) => break Some(start),
+                        None => path.push(p),
+                    },
+                    None => break None,
+                }
+            };
+            let Some(start) = cycle_start else {
+                break first_open;
+            };
+            // path[j + 1] envies path[j], and path[start] envies the last group.
+            let cycle = &path[start..];
+            let taken: Vec<usize> = (0..cycle.len()).map(|j| if j == 0 { cycle[cycle.len() - 1] } else { cycle[j - 1] }).collect();
+            let old_bundles = bundles.clone();
+            let old_values = values.clone();
+            for (&p, &from) in cycle.iter().zip(&taken) {
+                bundles[p] = old_bundles[from].clone();
+                for row in 0..num_groups {
+                    values[row][p] = old_values[row][from];
+                }
             }
-            // println!("aval_items_for_each_group {:?}", aval_items_for_each_group);
+        };
+        bundles[receiver].push(item);
+        for (p, row) in values.iter_mut().enumerate() {
+            row[receiver] = bundle_value(solver, &groups[p], &bundles[receiver], preferences)?;
+        }
+    }
 
-            let (max_weight, assignments) = compute_max_weight_matching(groups[p].clone(), aval_items_for_each_group.clone(), &preferences, match_size);
+    let mut utility_list: Vec<W> = Vec::new();
+    let mut assignment_list: Vec<Assignment<W>> = Vec::new();
+    for (group, bundle) in groups.iter().zip(&bundles) {
+        let (utility, assignment) = compute_max_weight_assignment_with(solver, group.clone(), bundle.clone(), preferences, group.len().min(bundle.len()))?;
+        utility_list.push(utility);
+        assignment_list.push(assignment);
+    }
+    let allocation = bundles.into_iter().map(|bundle| bundle.into_iter().collect()).collect();
+    Ok((allocation, utility_list, assignment_list))
+}
 
-            // println!("max_weight {:?}", max_weight);
+/// Optimal matching of one group's bundle during round robin.
+enum GroupMatching<W: Weight> {
+    /// Kept between picks, so a pick is a single augmenting path instead of a new
+    /// matching of one more item.
+    Warm(IncrementalMatching<W>),
+    /// Members of unit capacity whose values are all zero or one, matched by
+    /// alternating paths when the solver `supports_binary`: the bundle's value is a
+    /// matroid rank function, so a pick adds one exactly when such a path reaches the
+    /// item. Once no item adds one, round robin hands the group an item instead.
+    Binary { group: Vec<usize>, tie_break: TieBreak, matching: BinaryMatching },
+    /// Solved again on every pick.
+    Cold { group: Vec<usize>, capacities: Vec<usize>, tie_break: TieBreak, weight: W, assignment: MultiAssignment<W> },
+}
 
-            let new_allocation_p = assignments;
-            let diff: HashSet<usize> = allocation[p].symmetric_difference(&new_allocation_p).cloned().collect();
-            
-            // println!("allocation[p] {:?}", allocation[p]);
-            // println!("new_allocation_p {:?}", new_allocation_p);
+impl<W: Weight> GroupMatching<W> {
+    fn new<P: Preferences<W> + ?Sized, S: MatchingSolver<W>>(solver: &S, tie_break: &TieBreak, group: Vec<usize>, capacities: &[usize], units: &[usize], preferences: &P) -> Result<Self, MatchingError> {
+        let items: Vec<usize> = (0..units.len()).collect();
+        preferences.validate(&group, &items)?;
+        if solver.supports_binary() {
+            if let Some(matching) = binary_matching(&group, capacities, units.len(), preferences) {
+                return Ok(GroupMatching::Binary { group, tie_break: tie_break.clone(), matching });
+            }
+        }
+        if solver.supports_warm_start() {
+            let mut matching = IncrementalMatching::with_capacities(group, items, capacities, units, preferences)?;
+            matching.set_tie_break(tie_break.clone());
+            Ok(GroupMatching::Warm(matching))
+        } else {
+            if let Some(&agent) = group.iter().find(|&&agent| agent >= capacities.len()) {
+                return Err(MatchingError::MissingCapacity { agent, num_agents: capacities.len() });
+            }
+            Ok(GroupMatching::Cold { group, capacities: capacities.to_vec(), tie_break: tie_break.clone(), weight: W::zero(), assignment: MultiAssignment::new() })
+        }
+    }
 
-            // println!("diff {:?}",diff);
-            // println!("diff.len() {}",diff.len());
-            assert!(diff.len() == 1, "diff.len() == 1");
+    /// Adds the available unit that gives the best matching of one more unit, or
+    /// `None` when the group has no larger matching. `remaining` holds the units of
+    /// every item that no group has taken yet.
+    fn pick<P: Preferences<W> + ?Sized, S: MatchingSolver<W>>(&mut self, solver: &S, bundle: &HashMap<usize, usize>, remaining: &[usize], preferences: &P) -> Result<Option<usize>, MatchingError> {
+        match self {
+            GroupMatching::Warm(matching) => Ok(matching.augment()),
+            GroupMatching::Binary { group, tie_break, matching } => {
+                // Every member already holds an item.
+                if matching.units() >= group.len() {
+                    return Ok(None);
+                }
+                // An item that adds one, as the weighted solvers would pick it. Once none
+                // is left none comes back, as the matching no longer changes.
+                let improving = matching.improving();
+                let item = (0..remaining.len())
+                    .filter(|&item| remaining[item] > 0 && improving[item])
+                    .min_by_key(|&item| tie_break.key(item));
+                if let Some(item) = item {
+                    matching.add(item);
+                }
+                Ok(item)
+            }
+            GroupMatching::Cold { group, capacities, tie_break, weight, assignment } => {
+                let size = bundle.values().sum::<usize>() + 1;
+                let mut held = vec![0; remaining.len()];
+                for (&item, &count) in bundle {
+                    held[item] = count;
+                }
+                let solve = |units: &[usize]| {
+                    let candidates: Vec<usize> = (0..units.len()).filter(|&item| units[item] > 0).collect();
+                    compute_max_weight_capacitated_matching_with(solver, group.clone(), candidates, capacities, units, preferences, size)
+                };
 
-            allocation[p] = new_allocation_p;
-            utility_list[p] = max_weight;
+                // The group may use the units it holds and those still available.
+                let units: Vec<usize> = held.iter().zip(remaining).map(|(held, left)| held + left).collect();
+                // Every member already uses as many items as it may.
+                let (max_weight, new_assignment) = match solve(&units) {
+                    Err(MatchingError::InfeasibleSize { .. }) => return Ok(None),
+                    result => result?,
+                };
 
-            if !diff.is_empty() {
-                if let Some(&item) = diff.iter().next() {
-                    if let Some(pos) = available_items.iter().position(|&x| x == item) {
-                        available_items.remove(pos);
+                // Some optimal matching of one more unit keeps the whole bundle, but the
+                // solver may have found another one, so each item is tried on its own then,
+                // as under a tie-breaking policy, which needs every item of the best value.
+                let matched = |assignment: &MultiAssignment<W>| assignment.values().map(Vec::len).sum::<usize>();
+                let mut used = vec![0; remaining.len()];
+                for &(item, _) in new_assignment.values().flatten() {
+                    used[item] += 1;
+                }
+                let (item, max_weight, new_assignment) = if *tie_break == TieBreak::Solver && matched(&new_assignment) == size && used.iter().zip(&held).all(|(used, held)| used >= held) {
+                    let item = (0..used.len())
+                        .find(|&item| used[item] > held[item])
+                        .ok_or_else(|| MatchingError::BrokenInvariant("a larger matching holds no new unit".to_string()))?;
+                    (item, max_weight, new_assignment)
+                } else {
+                    let mut best: Option<(usize, W, MultiAssignment<W>)> = None;
+                    for item in (0..remaining.len()).filter(|&item| remaining[item] > 0) {
+                        let mut units = held.clone();
+                        units[item] += 1;
+                        let (max_weight, new_assignment) = solve(&units)?;
+                        // No member can use one more unit of this item.
+                        if matched(&new_assignment) < size {
+                            continue;
+                        }
+                        let better = best.as_ref().is_none_or(|&(best_item, best_weight, _)| {
+                            best_weight.definitely_lt(max_weight)
+                                || (!max_weight.definitely_lt(best_weight) && tie_break.key(item) < tie_break.key(best_item))
+                        });
+                        if better {
+                            best = Some((item, max_weight, new_assignment));
+                        }
+                    }
+                    match best {
+                        Some(best) => best,
+                        None => return Ok(None),
                     }
+                };
+                *weight = max_weight;
+                *assignment = new_assignment;
+                Ok(Some(item))
+            }
+        }
+    }
+
+    /// Withdraws a unit another group has taken.
+    fn remove_item(&mut self, item: usize) {
+        if let GroupMatching::Warm(matching) = self {
+            matching.remove_item(item);
+        }
+    }
+
+    fn weight(&self) -> W {
+        match self {
+            GroupMatching::Warm(matching) => matching.weight(),
+            GroupMatching::Binary { matching, .. } => (0..matching.size()).fold(W::zero(), |acc, _| acc + W::one()),
+            GroupMatching::Cold { weight, .. } => *weight,
+        }
+    }
+
+    fn assignment(&self) -> MultiAssignment<W> {
+        match self {
+            GroupMatching::Warm(matching) => matching.multi_assignment(),
+            GroupMatching::Binary { group, matching, .. } => {
+                let mut assignment = MultiAssignment::new();
+                for (i, item) in matching.pairs() {
+                    let value = if matching.values(i, item) { W::one() } else { W::zero() };
+                    assignment.entry(group[i]).or_insert_with(Vec::new).push((item, value));
                 }
-            } 
+                assignment
+            }
+            GroupMatching::Cold { assignment, .. } => assignment.clone(),
         }
     }
-    (allocation, utility_list)
 }
 
+// Matching of a group whose members have unit capacity and value every item at zero
+// or one, an item missing from sparse preferences counting as zero, or None when the
+// weighted solvers are needed
+fn binary_matching<W: Weight, P: Preferences<W> + ?Sized>(group: &[usize], capacities: &[usize], num_items: usize, preferences: &P) -> Option<BinaryMatching> {
+    if group.iter().any(|&agent| capacities.get(agent) != Some(&1)) {
+        return None;
+    }
+    let mut adjacency = Vec::with_capacity(group.len());
+    for &agent in group {
+        let mut valued = Vec::new();
+        for item in 0..num_items {
+            match preferences.value(agent, item) {
+                Some(value) if value.is_one() => valued.push(item),
+                Some(value) if !value.is_zero() => return None,
+                _ => {}
+            }
+        }
+        adjacency.push(valued);
+    }
+    Some(BinaryMatching::new(adjacency, num_items))
+}
 
 
 
@@ -115,8 +683,490 @@ mod tests {
 
         //println!("groups: {:?}", groups);
 
-        let (allocation, utility_list) = round_robin_allocation_by_group(num_items, num_groups, n_each, &groups, &preferences);
+        let (allocation, utility_list, assignment_list) = round_robin_allocation_by_group(num_items, num_groups, n_each, &groups, &preferences).unwrap();
         println!("allocation \n {:?}", allocation);
         println!("utility_list \n {:?}", utility_list);
+        println!("assignment_list \n {:?}", assignment_list);
         }
+
+    #[test]
+    fn test_round_robin_utilities_are_optimal() {
+        use crate::matching::compute_max_weight_matching;
+        use rand::Rng;
+
+        let n_each: usize = 3;
+        let num_groups: usize = 3;
+        let num_items: usize = 12;
+
+        let groups: Vec<Vec<usize>> = (0..num_groups).map(|i| (0..n_each).map(|j| i * n_each + j).collect()).collect();
+        let mut rng = rand::thread_rng();
+        let preferences: Vec<Vec<f64>> = (0..n_each * num_groups).map(|_| (0..num_items).map(|_| rng.gen()).collect()).collect();
+
+        let (allocation, utility_list, assignment_list) = round_robin_allocation_by_group(num_items, num_groups, n_each, &groups, &preferences).unwrap();
+        for p in 0..num_groups {
+            assert_eq!(allocation[p].len(), n_each);
+            // Every member holds one item of the bundle and the values add up to the utility.
+            let items: HashSet<usize> = assignment_list[p].values().map(|&(item, _)| item).collect();
+            assert_eq!(items, allocation[p]);
+            for (&agent, &(item, value)) in &assignment_list[p] {
+                assert!(groups[p].contains(&agent));
+                assert_eq!(value, preferences[agent][item]);
+            }
+            let total: f64 = assignment_list[p].values().map(|&(_, value)| value).sum();
+            assert!((utility_list[p] - total).abs() < 1e-9);
+            let (max_weight, _) = compute_max_weight_matching(groups[p].clone(), allocation[p].iter().copied().collect(), &preferences, n_each).unwrap();
+            assert!((utility_list[p] - max_weight).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_round_robin_solvers_agree() {
+        use crate::solver::KuhnMunkresSolver;
+        use rand::Rng;
+
+        let n_each: usize = 3;
+        let num_groups: usize = 3;
+        let num_items: usize = 10;
+
+        let groups: Vec<Vec<usize>> = (0..num_groups).map(|i| (0..n_each).map(|j| i * n_each + j).collect()).collect();
+        let mut rng = rand::thread_rng();
+        let preferences: Vec<Vec<f64>> = (0..n_each * num_groups).map(|_| (0..num_items).map(|_| rng.gen()).collect()).collect();
+
+        let (allocation, utility_list, _) = round_robin_allocation_by_group(num_items, num_groups, n_each, &groups, &preferences).unwrap();
+        let (km_allocation, km_utility_list, _) = round_robin_allocation_by_group_with(&KuhnMunkresSolver, num_items, num_groups, n_each, &groups, &preferences).unwrap();
+        assert_eq!(allocation, km_allocation);
+        for p in 0..num_groups {
+            assert!((utility_list[p] - km_utility_list[p]).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_round_robin_sparse_preferences() {
+        use crate::preferences::SparsePreferences;
+        use crate::solver::KuhnMunkresSolver;
+
+        // Every agent values two items only. Group 1 has no use for item 2 by the time it
+        // picks in the second round, but still takes its turn.
+        let groups = vec![vec![0, 1], vec![2, 3]];
+        let preferences = SparsePreferences::new(vec![
+            vec![(0, 5), (1, 4)],
+            vec![(0, 3), (2, 1)],
+            vec![(0, 6), (1, 1)],
+            vec![(1, 7), (3, 8)],
+        ]);
+
+        let (allocation, utility_list, assignment_list) = round_robin_allocation_by_group(5, 2, 2, &groups, &preferences).unwrap();
+        assert_eq!(allocation, vec![HashSet::from([0, 1]), HashSet::from([2, 3])]);
+        assert_eq!(utility_list, vec![7, 8]);
+        assert_eq!(assignment_list[1], HashMap::from([(3, (3, 8))]));
+
+        let (km_allocation, km_utility_list, _) = round_robin_allocation_by_group_with(&KuhnMunkresSolver, 5, 2, 2, &groups, &preferences).unwrap();
+        assert_eq!(km_allocation, allocation);
+        assert_eq!(km_utility_list, utility_list);
+    }
+
+    #[test]
+    fn test_round_robin_chores_and_mixed_valuations() {
+        use crate::matching::compute_max_weight_matching;
+        use crate::solver::KuhnMunkresSolver;
+        use rand::Rng;
+
+        // Every agent dislikes every item; each group takes the cheapest chore left.
+        let groups = vec![vec![0, 1], vec![2, 3]];
+        let chores: Vec<Vec<i64>> = vec![
+            vec![-1, -5, -3, -8],
+            vec![-2, -6, -4, -7],
+            vec![-9, -2, -5, -1],
+            vec![-3, -8, -6, -4],
+        ];
+        let (allocation, utility_list, _) = round_robin_allocation_by_group(4, 2, 2, &groups, &chores).unwrap();
+        assert_eq!(allocation, vec![HashSet::from([0, 2]), HashSet::from([1, 3])]);
+        assert_eq!(utility_list, vec![-5, -6]);
+
+        // Goods, chores and mixed items: every bundle is fully handled at its best.
+        let n_each: usize = 3;
+        let num_groups: usize = 3;
+        let num_items: usize = 8;
+        let groups: Vec<Vec<usize>> = (0..num_groups).map(|i| (0..n_each).map(|j| i * n_each + j).collect()).collect();
+        let mut rng = rand::thread_rng();
+        for range in [0..100, -100..0, -100..100] {
+            let preferences: Vec<Vec<i64>> = (0..n_each * num_groups).map(|_| (0..num_items).map(|_| rng.gen_range(range.clone())).collect()).collect();
+            // Ties may make the two solvers pick differently, so each is checked on its own.
+            for (allocation, utility_list, assignment_list) in [
+                round_robin_allocation_by_group(num_items, num_groups, n_each, &groups, &preferences).unwrap(),
+                round_robin_allocation_by_group_with(&KuhnMunkresSolver, num_items, num_groups, n_each, &groups, &preferences).unwrap(),
+            ] {
+                assert_eq!(allocation.iter().map(HashSet::len).sum::<usize>(), num_items);
+                for p in 0..num_groups {
+                    assert_eq!(assignment_list[p].len(), allocation[p].len());
+                    let (max_weight, _) = compute_max_weight_matching(groups[p].clone(), allocation[p].iter().copied().collect(), &preferences, allocation[p].len()).unwrap();
+                    assert_eq!(utility_list[p], max_weight);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_robin_allocation_of_units() {
+        use crate::solver::KuhnMunkresSolver;
+
+        // Everybody prefers item 0, which has three seats; group 0 takes two of them.
+        let groups = vec![vec![0, 1], vec![2, 3]];
+        let preferences: Vec<Vec<i64>> = vec![vec![10, 1], vec![9, 2], vec![8, 3], vec![7, 4]];
+        let expected = vec![HashMap::from([(0, 2)]), HashMap::from([(0, 1), (1, 1)])];
+        let (allocation, utility_list, assignment_list) = round_robin_allocation_of_units(2, &[3, 1], 2, 2, &groups, &preferences).unwrap();
+        assert_eq!(allocation, expected);
+        assert_eq!(utility_list, vec![19, 12]);
+        assert_eq!(assignment_list[0], HashMap::from([(0, (0, 10)), (1, (0, 9))]));
+
+        let (km_allocation, km_utility_list, _) = round_robin_allocation_of_units_with(&KuhnMunkresSolver, 2, &[3, 1], 2, 2, &groups, &preferences).unwrap();
+        assert_eq!(km_allocation, expected);
+        assert_eq!(km_utility_list, utility_list);
+
+        // Only the units there are get allocated.
+        let (allocation, _, _) = round_robin_allocation_of_units(2, &[1, 2], 2, 2, &groups, &preferences).unwrap();
+        assert_eq!(allocation, vec![HashMap::from([(0, 1), (1, 1)]), HashMap::from([(1, 1)])]);
+        assert_eq!(
+            round_robin_allocation_of_units(3, &[1, 2], 2, 2, &groups, &preferences),
+            Err(MatchingError::MissingUnits { item: 2, num_items: 3 })
+        );
+    }
+
+    #[test]
+    fn test_round_robin_allocation_capacitated() {
+        use crate::solver::KuhnMunkresSolver;
+        use rand::Rng;
+
+        // Single-member groups whose members use two items each.
+        let groups = vec![vec![0], vec![1]];
+        let preferences: Vec<Vec<i64>> = vec![vec![5, 4, 3, 1], vec![4, 5, 1, 3]];
+        for (allocation, utility_list, assignment_list) in [
+            round_robin_allocation_capacitated(4, &[1; 4], &[2, 2], 2, 2, &groups, &preferences).unwrap(),
+            round_robin_allocation_capacitated_with(&KuhnMunkresSolver, 4, &[1; 4], &[2, 2], 2, 2, &groups, &preferences).unwrap(),
+        ] {
+            assert_eq!(allocation, vec![HashMap::from([(0, 1), (2, 1)]), HashMap::from([(1, 1), (3, 1)])]);
+            assert_eq!(utility_list, vec![8, 8]);
+            assert_eq!(assignment_list[0], MultiAssignment::from([(0, vec![(0, 5), (2, 3)])]));
+            assert_eq!(assignment_list[1], MultiAssignment::from([(1, vec![(1, 5), (3, 3)])]));
+        }
+
+        // A member never uses two units of the same item, so the second unit of item 0
+        // adds nothing.
+        let (allocation, utility_list, _) = round_robin_allocation_capacitated(2, &[2, 1], &[2], 1, 3, &[vec![0]], &vec![vec![10, 1]]).unwrap();
+        assert_eq!(allocation, vec![HashMap::from([(0, 2), (1, 1)])]);
+        assert_eq!(utility_list, vec![11]);
+        assert_eq!(
+            round_robin_allocation_capacitated(4, &[1; 4], &[2], 2, 2, &groups, &preferences),
+            Err(MatchingError::MissingCapacity { agent: 1, num_agents: 1 })
+        );
+
+        // Utilities are optimal for the bundles, with both solvers.
+        let mut rng = rand::thread_rng();
+        let groups: Vec<Vec<usize>> = vec![vec![0, 1], vec![2, 3]];
+        for _ in 0..10 {
+            let capacities: Vec<usize> = (0..4).map(|_| rng.gen_range(1..4)).collect();
+            let preferences: Vec<Vec<i64>> = (0..4).map(|_| (0..10).map(|_| rng.gen_range(0..100)).collect()).collect();
+            for (allocation, utility_list, assignment_list) in [
+                round_robin_allocation_capacitated(10, &[1; 10], &capacities, 2, 4, &groups, &preferences).unwrap(),
+                round_robin_allocation_capacitated_with(&KuhnMunkresSolver, 10, &[1; 10], &capacities, 2, 4, &groups, &preferences).unwrap(),
+            ] {
+                for p in 0..2 {
+                    let items: Vec<usize> = allocation[p].keys().copied().collect();
+                    let size = items.len().min(capacities[groups[p][0]] + capacities[groups[p][1]]);
+                    let (max_weight, _) = compute_max_weight_capacitated_matching_with(&SuccessiveShortestPathSolver, groups[p].clone(), items, &capacities, &[1; 10], &preferences, size).unwrap();
+                    assert_eq!(utility_list[p], max_weight);
+                    assert!(assignment_list[p].iter().all(|(&agent, items)| items.len() <= capacities[agent]));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_robin_tie_break() {
+        use crate::solver::KuhnMunkresSolver;
+        use rand::Rng;
+
+        // Every item has the same value, so the policy alone decides.
+        let groups = vec![vec![0], vec![1]];
+        let preferences: Vec<Vec<i64>> = vec![vec![1; 4], vec![1; 4]];
+        let (allocation, _, _) = round_robin_allocation_by_group_with_tie_break(&SuccessiveShortestPathSolver, &TieBreak::SmallestItems, 4, 2, 2, &groups, &preferences).unwrap();
+        assert_eq!(allocation, vec![HashSet::from([0, 2]), HashSet::from([1, 3])]);
+        let demand = TieBreak::LowestDemand(vec![3, 2, 1, 0]);
+        let (allocation, _, _) = round_robin_allocation_by_group_with_tie_break(&KuhnMunkresSolver, &demand, 4, 2, 2, &groups, &preferences).unwrap();
+        assert_eq!(allocation, vec![HashSet::from([3, 1]), HashSet::from([2, 0])]);
+
+        // With many ties, both solvers give the same allocation under every policy.
+        let n_each: usize = 2;
+        let num_groups: usize = 3;
+        let num_items: usize = 9;
+        let groups: Vec<Vec<usize>> = (0..num_groups).map(|i| (0..n_each).map(|j| i * n_each + j).collect()).collect();
+        let mut rng = rand::thread_rng();
+        for _ in 0..10 {
+            let preferences: Vec<Vec<i64>> = (0..n_each * num_groups).map(|_| (0..num_items).map(|_| rng.gen_range(0..3)).collect()).collect();
+            for tie_break in [TieBreak::SmallestItems, TieBreak::LowestDemand((0..num_items).map(|_| rng.gen_range(0..4)).collect()), TieBreak::Random(rng.gen())] {
+                let ssp = round_robin_allocation_by_group_with_tie_break(&SuccessiveShortestPathSolver, &tie_break, num_items, num_groups, n_each, &groups, &preferences).unwrap();
+                let km = round_robin_allocation_by_group_with_tie_break(&KuhnMunkresSolver, &tie_break, num_items, num_groups, n_each, &groups, &preferences).unwrap();
+                assert_eq!(ssp.0, km.0);
+                assert_eq!(ssp.1, km.1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_robin_picking_sequence() {
+        let groups = vec![vec![0, 1], vec![2, 3]];
+        let preferences: Vec<Vec<i64>> = vec![vec![4, 3, 2, 1]; 4];
+        let allocate = |sequence: &PickingSequence| {
+            round_robin_allocation_by_group_with_sequence(&SuccessiveShortestPathSolver, &TieBreak::SmallestItems, sequence, 4, 2, 2, &groups, &preferences)
+        };
+
+        let (allocation, utility_list, _) = allocate(&PickingSequence::RoundRobin).unwrap();
+        assert_eq!(allocation, vec![HashSet::from([0, 2]), HashSet::from([1, 3])]);
+        assert_eq!(utility_list, vec![6, 4]);
+        let (allocation, utility_list, _) = allocate(&PickingSequence::Snake).unwrap();
+        assert_eq!(allocation, vec![HashSet::from([0, 3]), HashSet::from([1, 2])]);
+        assert_eq!(utility_list, vec![5, 5]);
+        let (allocation, _, _) = allocate(&PickingSequence::Explicit(vec![1, 1, 0])).unwrap();
+        assert_eq!(allocation, vec![HashSet::from([2, 3]), HashSet::from([0, 1])]);
+
+        // Once group 1 is full nobody picks any more.
+        let (allocation, utility_list, _) = allocate(&PickingSequence::Explicit(vec![1])).unwrap();
+        assert_eq!(allocation, vec![HashSet::new(), HashSet::from([0, 1])]);
+        assert_eq!(utility_list, vec![0, 7]);
+
+        // Every random sequence still hands out every item.
+        for seed in 0..5 {
+            let (allocation, _, _) = allocate(&PickingSequence::Random(seed)).unwrap();
+            assert_eq!(allocation.iter().map(HashSet::len).collect::<Vec<usize>>(), vec![2, 2]);
+        }
+
+        assert_eq!(allocate(&PickingSequence::Explicit(vec![0, 2])), Err(MatchingError::GroupOutOfRange { group: 2, num_groups: 2 }));
+    }
+
+    #[test]
+    fn test_round_robin_with_quotas() {
+        // A group of one that takes one item and a group of three that takes three.
+        let groups = vec![vec![0], vec![1, 2, 3]];
+        let preferences: Vec<Vec<i64>> = vec![vec![5, 4, 3, 2, 1]; 4];
+        let (allocation, utility_list, _) =
+            round_robin_allocation_with_quotas(&SuccessiveShortestPathSolver, &TieBreak::SmallestItems, &PickingSequence::RoundRobin, 5, &[1, 3], &groups, &preferences).unwrap();
+        assert_eq!(allocation, vec![HashSet::from([0]), HashSet::from([1, 2, 3])]);
+        assert_eq!(utility_list, vec![5, 9]);
+
+        // Quotas apart from the sizes: the group of three stops at one item.
+        let (allocation, utility_list, _) =
+            round_robin_allocation_with_quotas(&SuccessiveShortestPathSolver, &TieBreak::SmallestItems, &PickingSequence::RoundRobin, 5, &[1, 1], &groups, &preferences).unwrap();
+        assert_eq!(allocation, vec![HashSet::from([0]), HashSet::from([1])]);
+        assert_eq!(utility_list, vec![5, 4]);
+
+        let binary: Vec<Vec<i64>> = vec![vec![1; 4]; 3];
+        let (allocation, utility_list, _) = yankee_swap_allocation_with_quotas(4, &[1, 2], &[vec![0], vec![1, 2]], &binary).unwrap();
+        assert_eq!(allocation.iter().map(HashSet::len).collect::<Vec<usize>>(), vec![1, 2]);
+        assert_eq!(utility_list, vec![1, 2]);
+
+        assert_eq!(
+            round_robin_allocation_with_quotas::<i64, _, _>(&SuccessiveShortestPathSolver, &TieBreak::Solver, &PickingSequence::RoundRobin, 5, &[1, 1, 1], &groups, &preferences),
+            Err(MatchingError::MissingGroups { num_groups: 3, found: 2 })
+        );
+    }
+
+    #[test]
+    fn test_envy_cycle_allocation() {
+        use rand::Rng;
+
+        // Each group ends up with the item the other wants and envies it, so they
+        // swap before item 2 is handed out.
+        let groups = vec![vec![0], vec![1]];
+        let preferences: Vec<Vec<i64>> = vec![vec![1, 5, 0], vec![5, 1, 0]];
+        let (allocation, utility_list, assignment_list) = envy_cycle_allocation(3, 2, 3, &groups, &preferences).unwrap();
+        assert_eq!(allocation, vec![HashSet::from([1, 2]), HashSet::from([0])]);
+        assert_eq!(utility_list, vec![5, 5]);
+        assert_eq!(assignment_list[1].get(&1), Some(&(0, 5)));
+
+        // Full groups take nothing more.
+        let (allocation, _, _) = envy_cycle_allocation(3, 2, 1, &groups, &preferences).unwrap();
+        assert_eq!(allocation.iter().map(HashSet::len).collect::<Vec<usize>>(), vec![1, 1]);
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..20 {
+            let num_items = rng.gen_range(1..9);
+            let groups = vec![vec![0, 1], vec![2], vec![3, 4, 5]];
+            let preferences: Vec<Vec<i64>> = (0..6).map(|_| (0..num_items).map(|_| rng.gen_range(0..10)).collect()).collect();
+            let (allocation, utility_list, _) =
+                envy_cycle_allocation_with_quotas(&SuccessiveShortestPathSolver, num_items, &[num_items; 3], &groups, &preferences).unwrap();
+            assert_eq!(allocation.iter().map(HashSet::len).sum::<usize>(), num_items);
+            for (p, bundle) in allocation.iter().enumerate() {
+                let bundle: Vec<usize> = bundle.iter().copied().collect();
+                assert_eq!(bundle_value(&SuccessiveShortestPathSolver, &groups[p], &bundle, &preferences), Ok(utility_list[p]));
+            }
+            assert_eq!(is_weighted_ef1(&allocation, &[1, 1, 1], &groups, &preferences), Ok(true));
+        }
+    }
+
+    #[test]
+    fn test_weighted_round_robin_allocation() {
+        use rand::Rng;
+
+        // Group 1 is entitled to twice as much, so the picks go 0 1 1 0 1 1.
+        let groups = vec![vec![0, 1], vec![2, 3, 4, 5]];
+        let preferences: Vec<Vec<i64>> = vec![vec![6, 5, 4, 3, 2, 1]; 6];
+        let (allocation, utility_list, _) =
+            weighted_round_robin_allocation(&SuccessiveShortestPathSolver, &TieBreak::SmallestItems, 6, &[2, 4], &[1, 2], &groups, &preferences).unwrap();
+        assert_eq!(allocation, vec![HashSet::from([0, 3]), HashSet::from([1, 2, 4, 5])]);
+        assert_eq!(utility_list, vec![9, 12]);
+        assert_eq!(is_weighted_ef1(&allocation, &[1, 2], &groups, &preferences), Ok(true));
+        // Group 0 values the best two of the other items at 11, 9 without item 0,
+        // against 1 for its own, which takes an entitlement nine times smaller.
+        let unfair = vec![HashSet::from([5]), HashSet::from([0, 1, 2, 3, 4])];
+        assert_eq!(is_weighted_ef1(&unfair, &[1, 1], &groups, &preferences), Ok(false));
+        assert_eq!(is_weighted_ef1(&unfair, &[1, 9], &groups, &preferences), Ok(true));
+        assert_eq!(is_weighted_ef1(&allocation, &[1], &groups, &preferences), Err(MatchingError::MissingEntitlements { num_groups: 2, found: 1 }));
+
+        // Groups whose members agree and fit any bundle value bundles additively,
+        // and the weighted picking sequence is weighted EF1 for them.
+        let mut rng = rand::thread_rng();
+        for _ in 0..20 {
+            let num_groups = 3;
+            let num_items = rng.gen_range(1..8);
+            let entitlements: Vec<i64> = (0..num_groups).map(|_| rng.gen_range(1..4)).collect();
+            let groups: Vec<Vec<usize>> = (0..num_groups).map(|p| (p * num_items..(p + 1) * num_items).collect()).collect();
+            let mut preferences: Vec<Vec<i64>> = Vec::new();
+            for _ in 0..num_groups {
+                let row: Vec<i64> = (0..num_items).map(|_| rng.gen_range(0..10)).collect();
+                preferences.extend(std::iter::repeat_n(row, num_items));
+            }
+            let (allocation, _, _) =
+                weighted_round_robin_allocation(&SuccessiveShortestPathSolver, &TieBreak::SmallestItems, num_items, &[num_items; 3], &entitlements, &groups, &preferences).unwrap();
+            assert_eq!(is_weighted_ef1(&allocation, &entitlements, &groups, &preferences), Ok(true));
+        }
+    }
+
+    #[test]
+    fn test_yankee_swap_allocation() {
+        use crate::matching::compute_max_weight_matching_up_to;
+        use rand::Rng;
+
+        // Group 1 only values item 0, so group 0 passes it on for item 1.
+        let groups = vec![vec![0], vec![1]];
+        let preferences: Vec<Vec<i64>> = vec![vec![1, 1], vec![1, 0]];
+        let (allocation, utility_list, assignment_list) = yankee_swap_allocation(2, 2, 1, &groups, &preferences).unwrap();
+        assert_eq!(allocation, vec![HashSet::from([1]), HashSet::from([0])]);
+        assert_eq!(utility_list, vec![1, 1]);
+        assert_eq!(assignment_list[1], HashMap::from([(1, (0, 1))]));
+
+        // The sorted utilities are the largest in leximin order of all allocations,
+        // found by brute force, and no group envies another by more than one item.
+        let n_each: usize = 2;
+        let num_groups: usize = 3;
+        let num_items: usize = 6;
+        let groups: Vec<Vec<usize>> = (0..num_groups).map(|i| (0..n_each).map(|j| i * n_each + j).collect()).collect();
+        let value = |preferences: &Vec<Vec<i64>>, p: usize, bundle: &[usize]| {
+            compute_max_weight_matching_up_to(groups[p].clone(), bundle.to_vec(), preferences, n_each).unwrap().0
+        };
+        let mut rng = rand::thread_rng();
+        for _ in 0..10 {
+            let preferences: Vec<Vec<i64>> = (0..n_each * num_groups).map(|_| (0..num_items).map(|_| rng.gen_range(0..2)).collect()).collect();
+            let (allocation, utility_list, _) = yankee_swap_allocation(num_items, num_groups, n_each, &groups, &preferences).unwrap();
+
+            let mut best: Vec<i64> = Vec::new();
+            for code in 0..(num_groups + 1).pow(num_items as u32) {
+                let mut bundles: Vec<Vec<usize>> = vec![Vec::new(); num_groups + 1];
+                let mut rest = code;
+                for item in 0..num_items {
+                    bundles[rest % (num_groups + 1)].push(item);
+                    rest /= num_groups + 1;
+                }
+                let mut utilities: Vec<i64> = (0..num_groups).map(|p| value(&preferences, p, &bundles[p])).collect();
+                utilities.sort_unstable();
+                if utilities > best {
+                    best = utilities;
+                }
+            }
+            let mut sorted = utility_list.clone();
+            sorted.sort_unstable();
+            assert_eq!(sorted, best);
+
+            for p in 0..num_groups {
+                let own: Vec<usize> = allocation[p].iter().copied().collect();
+                assert_eq!(value(&preferences, p, &own), utility_list[p]);
+                for bundle in &allocation {
+                    let other: Vec<usize> = bundle.iter().copied().collect();
+                    let ef1 = other.is_empty()
+                        || other.iter().any(|&item| {
+                            let without: Vec<usize> = other.iter().copied().filter(|&x| x != item).collect();
+                            value(&preferences, p, &without) <= utility_list[p]
+                        });
+                    assert!(ef1);
+                }
+            }
+        }
+
+        assert_eq!(yankee_swap_allocation::<i64, _>(2, 2, 1, &groups, &vec![vec![2, 0]; 6]), Err(MatchingError::NotBinary { agent: 0, item: 0 }));
+    }
+
+    #[test]
+    fn test_round_robin_binary_preferences() {
+        use crate::preferences::SparsePreferences;
+        use crate::solver::{BinarySolver, KuhnMunkresSolver};
+        use rand::Rng;
+
+        // Binary preferences take the unweighted path of the binary solver; doubled, they
+        // take the weighted one, and every policy picks the same items from both.
+        let n_each: usize = 3;
+        let num_groups: usize = 3;
+        let num_items: usize = 12;
+        let groups: Vec<Vec<usize>> = (0..num_groups).map(|i| (0..n_each).map(|j| i * n_each + j).collect()).collect();
+        let mut rng = rand::thread_rng();
+        for _ in 0..20 {
+            let preferences: Vec<Vec<i64>> = (0..n_each * num_groups).map(|_| (0..num_items).map(|_| rng.gen_range(0..2)).collect()).collect();
+            let doubled: Vec<Vec<i64>> = preferences.iter().map(|row| row.iter().map(|&w| 2 * w).collect()).collect();
+            for tie_break in [TieBreak::SmallestItems, TieBreak::Random(rng.gen())] {
+                let binary = round_robin_allocation_by_group_with_tie_break(&BinarySolver, &tie_break, num_items, num_groups, n_each, &groups, &preferences).unwrap();
+                let ssp = round_robin_allocation_by_group_with_tie_break(&SuccessiveShortestPathSolver, &tie_break, num_items, num_groups, n_each, &groups, &doubled).unwrap();
+                let km = round_robin_allocation_by_group_with_tie_break(&KuhnMunkresSolver, &tie_break, num_items, num_groups, n_each, &groups, &doubled).unwrap();
+                assert_eq!(binary.0, ssp.0);
+                assert_eq!(binary.0, km.0);
+                assert_eq!(binary.1.iter().map(|&u| 2 * u).collect::<Vec<i64>>(), ssp.1);
+                for (p, assignment) in binary.2.iter().enumerate() {
+                    assert_eq!(assignment.values().map(|&(item, value)| { assert!(binary.0[p].contains(&item)); value }).sum::<i64>(), binary.1[p]);
+                }
+
+                // Sparse approvals leave out the items of value zero. Once nothing adds to
+                // a group's value it still takes its turn, with the available item of the
+                // smallest key, on either path, so every group fills its quota.
+                let sparse = SparsePreferences::from_dense(&preferences);
+                let binary = round_robin_allocation_by_group_with_tie_break(&BinarySolver, &tie_break, num_items, num_groups, n_each, &groups, &sparse).unwrap();
+                let ssp = round_robin_allocation_by_group_with_tie_break(&SuccessiveShortestPathSolver, &tie_break, num_items, num_groups, n_each, &groups, &sparse).unwrap();
+                assert_eq!((&binary.0, &binary.1), (&ssp.0, &ssp.1));
+                assert!(binary.0.iter().all(|bundle| bundle.len() == n_each));
+                for (p, assignment) in binary.2.iter().enumerate() {
+                    assert_eq!(assignment.values().filter(|&&(item, value)| binary.0[p].contains(&item) && value == 1).count() as i64, binary.1[p]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_robin_errors() {
+        use crate::solver::KuhnMunkresSolver;
+
+        let groups = vec![vec![0], vec![1]];
+        let preferences = vec![vec![1.0, 2.0, 3.0], vec![3.0, f64::NAN, 1.0]];
+        assert_eq!(
+            round_robin_allocation_by_group(3, 3, 1, &groups, &preferences),
+            Err(MatchingError::MissingGroups { num_groups: 3, found: 2 })
+        );
+        assert_eq!(
+            round_robin_allocation_by_group(3, 2, 1, &groups, &preferences),
+            Err(MatchingError::NotANumber { agent: 1, item: 1 })
+        );
+        assert_eq!(
+            round_robin_allocation_by_group_with(&KuhnMunkresSolver, 4, 1, 1, &groups, &preferences),
+            Err(MatchingError::ItemOutOfRange { agent: 0, item: 3, num_items: 3 })
+        );
+        assert_eq!(
+            round_robin_allocation_by_group(3, 2, 1, &[vec![0], vec![2]], &preferences),
+            Err(MatchingError::AgentOutOfRange { agent: 2, num_agents: 2 })
+        );
+    }
 }
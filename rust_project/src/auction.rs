@@ -0,0 +1,199 @@
+use crate::error::MatchingError;
+use crate::weight::Weight;
+
+/// Maximum weight matching of size `k` of the dense `n x m` matrix `weights`, with
+/// `k <= n <= m`, by Bertsekas' forward/reverse auction for asymmetric assignment
+/// with epsilon-scaling.
+///
+/// Only the `n` rows bid, over the `m` columns and `n - k` slack columns that stand
+/// for the unmatched rows. Every row values a slack column one more than any entry,
+/// so an optimal assignment of the rows uses every slack column and matches exactly
+/// `k` rows to columns. Prices start at zero. Each phase runs the forward auction
+/// until every row is assigned, then lowers the prices of the columns left over with
+/// reverse iterations, down to the lowest price of an assigned column, so that the
+/// phase ends `epsilon`-optimal, i.e. within `n * epsilon` of the optimum. Exact
+/// weights are scaled by `n + 1` so that the last phase, at `epsilon = 1`, is exact on
+/// integers; floating point weights stop at `Weight::epsilon() / n`.
+///
+/// Fails with `Overflow` when the scaled weights of an exact type do not fit.
+pub(crate) fn auction_fixed_size_max_weight_matching<W: Weight>(weights: &[Vec<W>], k: usize) -> Result<(W, Vec<(usize, usize)>), MatchingError> {
+    let n = weights.len();
+    let m = weights.first().map_or(0, Vec::len);
+    if n == 0 {
+        return Ok((W::zero(), Vec::new()));
+    }
+    let columns = m + n - k;
+
+    let count = |times: usize| (0..times).fold(W::zero(), |acc, _| acc + W::one());
+    let exact = W::epsilon() == W::zero();
+    let (scale, last) = if exact { (count(n + 1), W::one()) } else { (W::one(), W::epsilon() / count(n)) };
+
+    let mut benefits: Vec<Vec<W>> = Vec::with_capacity(n);
+    let mut largest = W::zero();
+    for row in weights {
+        let mut scaled = Vec::with_capacity(columns);
+        for &w in row {
+            let w = w.checked_mul(scale).ok_or(MatchingError::Overflow)?;
+            if largest < w.abs() {
+                largest = w.abs();
+            }
+            scaled.push(w);
+        }
+        benefits.push(scaled);
+    }
+    let slack = largest.checked_add(scale).ok_or(MatchingError::Overflow)?;
+    // Prices and profits stay within a few times the spread of the benefits.
+    slack.checked_add(largest).and_then(|spread| spread.checked_mul(count(8))).ok_or(MatchingError::Overflow)?;
+    for row in &mut benefits {
+        row.resize(columns, slack);
+    }
+
+    let factor = count(5);
+    let mut epsilon = slack / factor;
+    if epsilon < last {
+        epsilon = last;
+    }
+    let mut prices = vec![W::zero(); columns];
+    let mut profits = vec![W::zero(); n];
+    let mut assigned: Vec<Option<usize>> = vec![None; n];
+    let mut owner: Vec<Option<usize>> = vec![None; columns];
+    loop {
+        // Prices carry over from the previous phase, the assignment starts afresh.
+        assigned.iter_mut().for_each(|column| *column = None);
+        owner.iter_mut().for_each(|row| *row = None);
+
+        // Forward: every unassigned row bids for its best column.
+        let mut unassigned: Vec<usize> = (0..n).rev().collect();
+        while let Some(row) = unassigned.pop() {
+            let mut best = 0;
+            let mut best_value = benefits[row][0] - prices[0];
+            let mut second_value: Option<W> = None;
+            for (column, &price) in prices.iter().enumerate().skip(1) {
+                let value = benefits[row][column] - price;
+                if best_value < value {
+                    second_value = Some(best_value);
+                    best = column;
+                    best_value = value;
+                } else if second_value.is_none_or(|second| second < value) {
+                    second_value = Some(value);
+                }
+            }
+
+            // Raise the price until the second best column is as good, plus epsilon.
+            let second_value = second_value.unwrap_or(best_value);
+            prices[best] = benefits[row][best] - second_value + epsilon;
+            profits[row] = second_value - epsilon;
+            if let Some(previous) = owner[best].replace(row) {
+                assigned[previous] = None;
+                unassigned.push(previous);
+            }
+            assigned[row] = Some(best);
+        }
+
+        // Reverse: a column left over above the lowest assigned price takes the row it
+        // is best for, or drops its price to that level.
+        let floor = assigned.iter().flatten().map(|&column| prices[column]).fold(None, |acc: Option<W>, price| match acc {
+            Some(low) if low <= price => Some(low),
+            _ => Some(price),
+        });
+        let Some(floor) = floor else {
+            break;
+        };
+        let mut left_over: Vec<usize> = (0..columns).filter(|&column| owner[column].is_none() && floor < prices[column]).collect();
+        while let Some(column) = left_over.pop() {
+            let mut best = 0;
+            let mut best_value = benefits[0][column] - profits[0];
+            let mut second_value: Option<W> = None;
+            for (row, &profit) in profits.iter().enumerate().skip(1) {
+                let value = benefits[row][column] - profit;
+                if best_value < value {
+                    second_value = Some(best_value);
+                    best = row;
+                    best_value = value;
+                } else if second_value.is_none_or(|second| second < value) {
+                    second_value = Some(value);
+                }
+            }
+            if best_value - epsilon <= floor {
+                prices[column] = floor;
+                continue;
+            }
+            let price = match second_value {
+                Some(second) if floor < second - epsilon => second - epsilon,
+                _ => floor,
+            };
+            prices[column] = price;
+            profits[best] = benefits[best][column] - price;
+            if let Some(previous) = assigned[best].replace(column) {
+                owner[previous] = None;
+                if floor < prices[previous] {
+                    left_over.push(previous);
+                }
+            }
+            owner[column] = Some(best);
+        }
+
+        if epsilon <= last {
+            break;
+        }
+        epsilon = epsilon / factor;
+        if epsilon < last {
+            epsilon = last;
+        }
+    }
+
+    let matched_pairs: Vec<(usize, usize)> = assigned
+        .into_iter()
+        .enumerate()
+        .filter_map(|(row, column)| column.filter(|&column| column < m).map(|column| (row, column)))
+        .collect();
+    let max_weight = matched_pairs.iter().fold(W::zero(), |acc, &(i, j)| acc + weights[i][j]);
+    Ok((max_weight, matched_pairs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::fixed_size_max_weight_matching;
+    use rand::Rng;
+
+    #[test]
+    fn test_auction_fixed_size_max_weight_matching() {
+        let weights: Vec<Vec<i64>> = vec![vec![3, 1, 0], vec![2, 2, 5]];
+        assert_eq!(auction_fixed_size_max_weight_matching(&weights, 0), Ok((0, vec![])));
+        assert_eq!(auction_fixed_size_max_weight_matching(&weights, 1), Ok((5, vec![(1, 2)])));
+        assert_eq!(auction_fixed_size_max_weight_matching(&weights, 2), Ok((8, vec![(0, 0), (1, 2)])));
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..50 {
+            let n = rng.gen_range(1..7);
+            let m = rng.gen_range(n..10);
+            let weights: Vec<Vec<i64>> = (0..n).map(|_| (0..m).map(|_| rng.gen_range(-5..20)).collect()).collect();
+            for k in 0..=n {
+                let (max_weight, matched) = auction_fixed_size_max_weight_matching(&weights, k).unwrap();
+                assert_eq!(max_weight, fixed_size_max_weight_matching(&weights, k).unwrap().0);
+                assert_eq!(matched.len(), k);
+            }
+
+            let weights: Vec<Vec<f64>> = (0..n).map(|_| (0..m).map(|_| rng.gen()).collect()).collect();
+            for k in 0..=n {
+                let (max_weight, matched) = auction_fixed_size_max_weight_matching(&weights, k).unwrap();
+                assert!((max_weight - fixed_size_max_weight_matching(&weights, k).unwrap().0).abs() < 1e-9);
+                assert_eq!(matched.len(), k);
+            }
+        }
+    }
+
+    #[test]
+    fn test_auction_large_weights() {
+        // Scaled by three, these still fit in an i64.
+        let weights: Vec<Vec<i64>> = vec![vec![1_000_000_000_000, 3], vec![999_999_999_999, 1_000_000_000_000]];
+        assert_eq!(auction_fixed_size_max_weight_matching(&weights, 2), Ok((2_000_000_000_000, vec![(0, 0), (1, 1)])));
+        assert_eq!(auction_fixed_size_max_weight_matching(&weights, 1).unwrap().0, 1_000_000_000_000);
+
+        let weights: Vec<Vec<i64>> = vec![vec![i64::MAX / 4, 0], vec![0, 1]];
+        assert_eq!(auction_fixed_size_max_weight_matching(&weights, 2), Err(MatchingError::Overflow));
+        let weights: Vec<Vec<i32>> = vec![vec![100_000_000, -100_000_000]; 2];
+        assert_eq!(auction_fixed_size_max_weight_matching(&weights, 1), Err(MatchingError::Overflow));
+    }
+}
@@ -0,0 +1,292 @@
+use crate::weight::Weight;
+use std::collections::VecDeque;
+
+
+/// Whether the graph of `rows`, given as `(column, weight)` pairs over `m` columns,
+/// has every weight zero or one and is either complete or lists the pairs of weight
+/// one only, as sparse approvals do; a pair left out then counts as zero.
+///
+/// A matching of size `k` of a complete such graph weighs `min(k, nu)`, where `nu` is
+/// the size of a maximum matching of its edges of weight one: any such matching of
+/// size up to `k` extends to size `k` by edges of weight zero. A graph of edges of
+/// weight one has no larger matching than `nu` to begin with. So the weighted solvers
+/// can be replaced by Hopcroft-Karp. A graph that lists some pairs of weight zero and
+/// leaves others out is not accepted, as those pairs may not complete a matching.
+pub(crate) fn is_binary<W: Weight>(rows: &[Vec<(usize, W)>], m: usize) -> bool {
+    rows.iter().all(|row| row.iter().all(|&(_, w)| w.is_zero() || w.is_one()))
+        && (rows.iter().all(|row| row.len() == m) || rows.iter().flatten().all(|&(_, w)| w.is_one()))
+}
+
+/// Maximum weight matching of size `k` of a graph `is_binary` accepts, as
+/// `(row, column)` pairs in increasing row order.
+pub(crate) fn binary_fixed_size_max_weight_matching<W: Weight>(rows: &[Vec<(usize, W)>], m: usize, k: usize) -> (W, Vec<(usize, usize)>) {
+    let adjacency: Vec<Vec<usize>> = rows.iter().map(|row| row.iter().filter(|&&(_, w)| w.is_one()).map(|&(j, _)| j).collect()).collect();
+    let row_match = hopcroft_karp(&adjacency, m);
+
+    let mut matched_pairs: Vec<(usize, usize)> =
+        row_match.iter().enumerate().filter_map(|(i, &j)| j.map(|j| (i, j))).take(k).collect();
+    let max_weight = matched_pairs.iter().fold(W::zero(), |acc, _| acc + W::one());
+
+    // Edges of weight zero make up the size of a complete graph.
+    if rows.iter().any(|row| row.len() < m) {
+        return (max_weight, matched_pairs);
+    }
+    let mut free_columns = vec![true; m];
+    let mut free_rows = vec![true; rows.len()];
+    for &(i, j) in &matched_pairs {
+        free_rows[i] = false;
+        free_columns[j] = false;
+    }
+    let fill: Vec<(usize, usize)> = (0..rows.len())
+        .filter(|&i| free_rows[i])
+        .zip((0..m).filter(|&j| free_columns[j]))
+        .take(k - matched_pairs.len())
+        .collect();
+    matched_pairs.extend(fill);
+    matched_pairs.sort_unstable();
+    (max_weight, matched_pairs)
+}
+
+/// Maximum cardinality matching of the bipartite graph whose row `i` is adjacent to
+/// the columns `adjacency[i]`, out of `m`: the column of every row, if any.
+pub(crate) fn hopcroft_karp(adjacency: &[Vec<usize>], m: usize) -> Vec<Option<usize>> {
+    let n = adjacency.len();
+    let mut row_match: Vec<Option<usize>> = vec![None; n];
+    let mut column_match: Vec<Option<usize>> = vec![None; m];
+    loop {
+        // Layers of the alternating paths from the free rows.
+        let mut layer = vec![usize::MAX; n];
+        let mut queue: VecDeque<usize> = (0..n).filter(|&i| row_match[i].is_none()).collect();
+        queue.iter().for_each(|&i| layer[i] = 0);
+        let mut found = false;
+        while let Some(i) = queue.pop_front() {
+            for &j in &adjacency[i] {
+                match column_match[j] {
+                    None => found = true,
+                    Some(next) if layer[next] == usize::MAX => {
+                        layer[next] = layer[i] + 1;
+                        queue.push_back(next);
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+        if !found {
+            return row_match;
+        }
+        // Vertex-disjoint augmenting paths along the layers.
+        for i in 0..n {
+            if row_match[i].is_none() {
+                augment_along_layers(i, adjacency, &mut layer, &mut row_match, &mut column_match);
+            }
+        }
+    }
+}
+
+fn augment_along_layers(i: usize, adjacency: &[Vec<usize>], layer: &mut [usize], row_match: &mut [Option<usize>], column_match: &mut [Option<usize>]) -> bool {
+    for &j in &adjacency[i] {
+        let reaches = match column_match[j] {
+            None => true,
+            Some(next) => layer[next] == layer[i].wrapping_add(1) && augment_along_layers(next, adjacency, layer, row_match, column_match),
+        };
+        if reaches {
+            row_match[i] = Some(j);
+            column_match[j] = Some(i);
+            return true;
+        }
+    }
+    // A dead end for the rest of the phase.
+    layer[i] = usize::MAX;
+    false
+}
+
+/// Maximum matching of the members whose valued items are `adjacency` to the items
+/// of `bundle`, as `(member, item)` pairs; its size is the value of the bundle.
+pub(crate) fn bundle_matching(adjacency: &[Vec<usize>], bundle: &[usize]) -> Vec<(usize, usize)> {
+    let columns: std::collections::HashMap<usize, usize> = bundle.iter().enumerate().map(|(j, &item)| (item, j)).collect();
+    let local: Vec<Vec<usize>> = adjacency.iter().map(|items| items.iter().filter_map(|item| columns.get(item).copied()).collect()).collect();
+    hopcroft_karp(&local, bundle.len())
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, j)| j.map(|j| (i, bundle[j])))
+        .collect()
+}
+
+/// Matching of a group with binary preferences to the units of the items it holds,
+/// kept between the picks of round robin.
+///
+/// The value of a bundle is the size of a maximum matching of the edges of value
+/// one, a matroid rank function, so one more unit adds at most one, and adds one
+/// exactly when an alternating path from an unmatched member reaches the item.
+#[derive(Clone, Debug)]
+pub(crate) struct BinaryMatching {
+    /// Items each member values at one.
+    adjacency: Vec<Vec<usize>>,
+    /// Units of each item the group holds.
+    held: Vec<usize>,
+    /// Item each member gets value from.
+    row_match: Vec<Option<usize>>,
+}
+
+impl BinaryMatching {
+    pub(crate) fn new(adjacency: Vec<Vec<usize>>, num_items: usize) -> Self {
+        let row_match = vec![None; adjacency.len()];
+        BinaryMatching { adjacency, held: vec![0; num_items], row_match }
+    }
+
+    /// Number of members who get value from an item.
+    pub(crate) fn size(&self) -> usize {
+        self.row_match.iter().flatten().count()
+    }
+
+    /// Whether member `i` values `item` at one.
+    pub(crate) fn values(&self, i: usize, item: usize) -> bool {
+        self.adjacency[i].contains(&item)
+    }
+
+    /// Number of units the group holds.
+    pub(crate) fn units(&self) -> usize {
+        self.held.iter().sum()
+    }
+
+    /// Whether one more unit of each item would add one to the value.
+    pub(crate) fn improving(&self) -> Vec<bool> {
+        let (reached, _) = self.search();
+        reached
+    }
+
+    /// Adds one unit of `item`, matched along an alternating path if one reaches it.
+    pub(crate) fn add(&mut self, item: usize) {
+        self.held[item] += 1;
+        let (reached, previous) = self.search();
+        if !reached[item] {
+            return;
+        }
+        // The path enters every item from a member and leaves it to a member that
+        // gets value from it; flipping it frees the last item and matches the first
+        // member.
+        let mut j = item;
+        while let Some(i) = previous[j] {
+            let next = self.row_match[i];
+            self.row_match[i] = Some(j);
+            match next {
+                Some(next) => j = next,
+                None => break,
+            }
+        }
+    }
+
+    /// Items reachable by an alternating path from a member without value, and the
+    /// member each was reached from.
+    fn search(&self) -> (Vec<bool>, Vec<Option<usize>>) {
+        let num_items = self.held.len();
+        let mut reached = vec![false; num_items];
+        let mut previous = vec![None; num_items];
+        let mut holders: Vec<Vec<usize>> = vec![Vec::new(); num_items];
+        for (i, &j) in self.row_match.iter().enumerate() {
+            if let Some(j) = j {
+                holders[j].push(i);
+            }
+        }
+        let mut visited = vec![false; self.adjacency.len()];
+        let mut queue: VecDeque<usize> = (0..self.adjacency.len()).filter(|&i| self.row_match[i].is_none()).collect();
+        queue.iter().for_each(|&i| visited[i] = true);
+        while let Some(i) = queue.pop_front() {
+            for &j in &self.adjacency[i] {
+                if reached[j] {
+                    continue;
+                }
+                reached[j] = true;
+                previous[j] = Some(i);
+                for &next in &holders[j] {
+                    if !visited[next] {
+                        visited[next] = true;
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+        (reached, previous)
+    }
+
+    /// Matched `(member, item)` pairs of every unit held, in increasing member order,
+    /// with the units no member values given to members without value.
+    pub(crate) fn pairs(&self) -> Vec<(usize, usize)> {
+        let mut spare = self.held.clone();
+        for &j in self.row_match.iter().flatten() {
+            spare[j] -= 1;
+        }
+        let mut spare_units = spare.iter().enumerate().flat_map(|(j, &count)| std::iter::repeat_n(j, count));
+        let mut pairs = Vec::new();
+        for (i, &j) in self.row_match.iter().enumerate() {
+            match j {
+                Some(j) => pairs.push((i, j)),
+                None => {
+                    if let Some(j) = spare_units.next() {
+                        pairs.push((i, j));
+                    }
+                }
+            }
+        }
+        pairs
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::{fixed_size_max_weight_matching, sparse_fixed_size_max_weight_matching};
+    use rand::Rng;
+
+    #[test]
+    fn test_binary_fixed_size_max_weight_matching() {
+        let adjacency = vec![vec![0, 1], vec![0], vec![2]];
+        let row_match = hopcroft_karp(&adjacency, 3);
+        assert_eq!(row_match, vec![Some(1), Some(0), Some(2)]);
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..50 {
+            let n = rng.gen_range(1..7);
+            let m = rng.gen_range(n..10);
+            let weights: Vec<Vec<i64>> = (0..n).map(|_| (0..m).map(|_| rng.gen_range(0..2)).collect()).collect();
+            let rows: Vec<Vec<(usize, i64)>> = weights.iter().map(|row| row.iter().copied().enumerate().collect()).collect();
+            assert!(is_binary(&rows, m));
+            for k in 0..=n {
+                let (max_weight, matched) = binary_fixed_size_max_weight_matching(&rows, m, k);
+                assert_eq!(max_weight, fixed_size_max_weight_matching(&weights, k).unwrap().0);
+                assert_eq!(matched.len(), k);
+                assert_eq!(matched.iter().map(|&(i, j)| weights[i][j]).sum::<i64>(), max_weight);
+            }
+        }
+        assert!(!is_binary(&[vec![(0, 2)]], 1));
+        assert!(!is_binary(&[vec![(0, 0)]], 2));
+
+        // Sparse approvals: the pairs left out count as zero and are never matched.
+        for _ in 0..50 {
+            let n = rng.gen_range(1..7);
+            let m = rng.gen_range(1..10);
+            let rows: Vec<Vec<(usize, i64)>> = (0..n).map(|_| (0..m).filter(|_| rng.gen()).map(|j| (j, 1)).collect()).collect();
+            assert!(is_binary(&rows, m));
+            for k in 0..=n.min(m) {
+                let (max_weight, matched) = binary_fixed_size_max_weight_matching(&rows, m, k);
+                assert_eq!((max_weight, matched.len()), (sparse_fixed_size_max_weight_matching(&rows, m, k).0, max_weight as usize));
+                assert!(matched.iter().all(|&(i, j)| rows[i].contains(&(j, 1))));
+            }
+        }
+    }
+
+    #[test]
+    fn test_binary_matching() {
+        // Member 0 values items 0 and 1, member 1 only item 0.
+        let mut matching = BinaryMatching::new(vec![vec![0, 1], vec![0]], 3);
+        assert_eq!(matching.improving(), vec![true, true, false]);
+        matching.add(1);
+        assert_eq!(matching.improving(), vec![true, false, false]);
+        matching.add(2);
+        assert_eq!((matching.size(), matching.units()), (1, 2));
+        matching.add(0);
+        assert_eq!(matching.size(), 2);
+        assert_eq!(matching.pairs(), vec![(0, 1), (1, 0)]);
+    }
+}
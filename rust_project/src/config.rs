@@ -1,9 +1,106 @@
 
 
+use crate::picking::PickingSequence;
+use crate::solver::Solver;
+use std::str::FromStr;
+
 pub struct Config {
-    pub n_each: usize,
+    /// Number of agents of each group.
+    pub group_sizes: Vec<usize>,
     pub num_groups: usize,
     pub num_items: usize,
+    pub solver: Solver,
+    pub valuations: Valuations,
+    pub ties: Ties,
+    pub allocation: Allocation,
+    pub sequence: PickingSequence,
+    /// Number of items each group takes, by default its size.
+    pub quotas: Vec<usize>,
+    /// Whether every allocation is compared with a local search for Nash welfare.
+    pub nash_benchmark: bool,
+}
+
+/// Sign of the random preferences drawn by the experiment binary, or values of zero
+/// and one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Valuations {
+    /// Values in `[0, 1)`.
+    #[default]
+    Goods,
+    /// Values in `(-1, 0]`.
+    Chores,
+    /// Values in `[-1, 1)`.
+    Mixed,
+    /// Values zero or one, each with probability one half.
+    Binary,
+}
+
+impl FromStr for Valuations {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "goods" => Ok(Valuations::Goods),
+            "chores" => Ok(Valuations::Chores),
+            "mixed" => Ok(Valuations::Mixed),
+            "binary" => Ok(Valuations::Binary),
+            _ => Err("invalid value for valuations"),
+        }
+    }
+}
+
+/// Allocation algorithm of the experiment binary; Yankee Swap needs binary
+/// valuations, and the exact maximum Nash welfare only suits a handful of items.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Allocation {
+    #[default]
+    RoundRobin,
+    YankeeSwap,
+    EnvyCycle,
+    MaxNashWelfare,
+    NashLocalSearch,
+}
+
+impl FromStr for Allocation {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "round-robin" | "rr" => Ok(Allocation::RoundRobin),
+            "yankee-swap" | "ys" => Ok(Allocation::YankeeSwap),
+            "envy-cycle" | "ece" => Ok(Allocation::EnvyCycle),
+            "max-nash-welfare" | "mnw" => Ok(Allocation::MaxNashWelfare),
+            "nash-local-search" | "mnw-local" => Ok(Allocation::NashLocalSearch),
+            _ => Err("invalid value for allocation"),
+        }
+    }
+}
+
+/// Tie-breaking policy of the experiment binary; `Demand` counts the agents of each
+/// instance who value an item positively.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Ties {
+    #[default]
+    Solver,
+    Smallest,
+    Demand,
+    Random(u64),
+}
+
+impl FromStr for Ties {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "solver" => Ok(Ties::Solver),
+            "smallest" => Ok(Ties::Smallest),
+            "demand" => Ok(Ties::Demand),
+            _ => match s.strip_prefix("random:") {
+                Some(seed) => seed.parse().map(Ties::Random).map_err(|_| "invalid seed for random ties"),
+                None => Err("invalid value for ties"),
+            },
+        }
+    }
 }
 
 impl Config {
@@ -12,25 +109,91 @@ impl Config {
             return Err("not enough arguments");
         }
 
-        let n_each = match args[1].parse() {
-            Ok(num) => num,
-            Err(_) => return Err("invalid value for n_each"),
-        };
-
         let num_groups = match args[2].parse() {
             Ok(num) => num,
-            Err(_) => return Err("invalid value for n_each"),
+            Err(_) => return Err("invalid value for num_groups"),
         };
 
+        let group_sizes = per_group(&args[1], num_groups).ok_or("invalid value for n_each")?;
+
         let num_items = match args[3].parse() {
             Ok(num) => num,
             Err(_) => return Err("invalid value for num_items"),
         };
 
+        let solver = match args.get(4) {
+            Some(arg) => arg.parse()?,
+            None => Solver::default(),
+        };
+
+        let valuations = match args.get(5) {
+            Some(arg) => arg.parse()?,
+            None => Valuations::default(),
+        };
+
+        let ties = match args.get(6) {
+            Some(arg) => arg.parse()?,
+            None => Ties::default(),
+        };
+
+        let allocation = match args.get(7) {
+            Some(arg) => arg.parse()?,
+            None => Allocation::default(),
+        };
+
+        let sequence = match args.get(8) {
+            Some(arg) => arg.parse()?,
+            None => PickingSequence::default(),
+        };
+
+        let quotas = match args.get(9) {
+            Some(arg) => per_group(arg, num_groups).ok_or("invalid value for quotas")?,
+            None => group_sizes.clone(),
+        };
+
+        let nash_benchmark = match args.get(10).map(String::as_str) {
+            Some("nash") => true,
+            Some("none") | None => false,
+            Some(_) => return Err("invalid value for benchmark"),
+        };
+
+        // Yankee Swap would skip every instance with a value other than zero or one.
+        if allocation == Allocation::YankeeSwap && valuations != Valuations::Binary {
+            return Err("yankee-swap needs binary valuations");
+        }
+
+        // The Nash welfare allocations and benchmark would skip every instance with a
+        // negative value.
+        if matches!(valuations, Valuations::Chores | Valuations::Mixed) {
+            if matches!(allocation, Allocation::MaxNashWelfare | Allocation::NashLocalSearch) {
+                return Err("Nash welfare allocations need goods or binary valuations");
+            }
+            if nash_benchmark {
+                return Err("the Nash benchmark needs goods or binary valuations");
+            }
+        }
+
         Ok(Config {
-            n_each,
+            group_sizes,
             num_groups,
             num_items,
+            solver,
+            valuations,
+            ties,
+            allocation,
+            sequence,
+            quotas,
+            nash_benchmark,
         })
     }
+}
+
+/// One number for every group, or a comma-separated list of `num_groups` numbers.
+fn per_group(arg: &str, num_groups: usize) -> Option<Vec<usize>> {
+    let values: Vec<usize> = arg.split(',').map(|value| value.trim().parse().ok()).collect::<Option<_>>()?;
+    match values.len() {
+        1 => Some(vec![values[0]; num_groups]),
+        len if len == num_groups => Some(values),
+        _ => None,
+    }
 }
\ No newline at end of file
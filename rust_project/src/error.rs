@@ -0,0 +1,72 @@
+use thiserror::Error;
+
+
+/// Reasons the matching and allocation code rejects an instance.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum MatchingError {
+    #[error("no matching of size {requested} exists, the largest has size {largest}")]
+    InfeasibleSize { requested: usize, largest: usize },
+
+    #[error("the weight matrix has {rows} rows but only {columns} columns")]
+    TooManyRows { rows: usize, columns: usize },
+
+    #[error("agent {agent} has no preferences, there are {num_agents} preference rows")]
+    AgentOutOfRange { agent: usize, num_agents: usize },
+
+    #[error("item {item} is out of range, agent {agent} has preferences for {num_items} items")]
+    ItemOutOfRange { agent: usize, item: usize, num_items: usize },
+
+    #[error("agent {agent} has preferences for {len} items, but agent 0 has {expected}")]
+    RaggedPreferences { agent: usize, len: usize, expected: usize },
+
+    #[error("the preference of agent {agent} for item {item} is NaN")]
+    NotANumber { agent: usize, item: usize },
+
+    #[error("the weights are too large to scale without overflow")]
+    Overflow,
+
+    #[error("the preference of agent {agent} for item {item} is neither zero nor one")]
+    NotBinary { agent: usize, item: usize },
+
+    #[error("the preference of agent {agent} for item {item} is negative, and Nash welfare needs goods")]
+    NegativeValuation { agent: usize, item: usize },
+
+    #[error("item {item} has no number of units, {num_items} items need one")]
+    MissingUnits { item: usize, num_items: usize },
+
+    #[error("agent {agent} has no capacity, only {num_agents} agents have one")]
+    MissingCapacity { agent: usize, num_agents: usize },
+
+    #[error("{num_groups} groups are allocated, but only {found} are given")]
+    MissingGroups { num_groups: usize, found: usize },
+
+    #[error("the flow network has {nodes} nodes, but {found} supplies are given")]
+    MissingSupplies { nodes: usize, found: usize },
+
+    #[error("the supplies exceed the demands by {excess} units")]
+    UnbalancedSupplies { excess: i64 },
+
+    #[error("the flow network has a cycle of negative cost")]
+    NegativeCycle,
+
+    #[error("the picking sequence names no group")]
+    EmptyPickingSequence,
+
+    #[error("the picking sequence names group {group}, but there are only {num_groups} groups")]
+    GroupOutOfRange { group: usize, num_groups: usize },
+
+    #[error("{num_groups} groups pick, but only {found} entitlements are given")]
+    MissingEntitlements { num_groups: usize, found: usize },
+
+    #[error("the entitlement of group {group} is not a positive number")]
+    InvalidEntitlement { group: usize },
+
+    #[error("the shift of a weighted picking sequence is not between 0 and 1")]
+    InvalidShift,
+
+    #[error("the certificate does not prove the matching optimal: {0}")]
+    InvalidCertificate(String),
+
+    #[error("broken invariant: {0}")]
+    BrokenInvariant(String),
+}
@@ -0,0 +1,184 @@
+use crate::allocation::bundle_value;
+use crate::error::MatchingError;
+use crate::preferences::Preferences;
+use crate::solver::{MatchingSolver, SuccessiveShortestPathSolver};
+use crate::weight::Weight;
+use std::collections::HashSet;
+
+
+/// How group `p` sees the bundle of group `q`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PairReport<W> {
+    pub p: usize,
+    pub q: usize,
+    /// `v_p(A_q) - v_p(A_p)` when positive, zero otherwise.
+    pub envy: W,
+    /// `v_p(A_p) >= v_p(A_q)`.
+    pub ef: bool,
+    /// Envy-free once the item of `A_q` that lowers its value the most is removed,
+    /// or, for chores, an item of `A_p`.
+    pub ef1: bool,
+    /// Envy-free whichever item of `A_q` is removed.
+    pub efx: bool,
+}
+
+/// How group `p` sees its own bundle against all the items.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GroupReport<W> {
+    pub p: usize,
+    /// `v_p(A_p)`.
+    pub utility: W,
+    /// `v_p` of all the items; the proportional share is this over the number of
+    /// groups.
+    pub whole: W,
+    /// `v_p(A_p)` is at least the proportional share.
+    pub prop: bool,
+    /// Proportional once one item is added to `A_p`, or, for chores, removed from it.
+    pub prop1: bool,
+}
+
+/// Envy and proportionality of an allocation between groups, where a group values a
+/// bundle at its maximum weight matching of `min(group size, bundle size)` pairs,
+/// as the experiments do.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FairnessReport<W> {
+    /// Every ordered pair of distinct groups, `p` first.
+    pub pairs: Vec<PairReport<W>>,
+    pub groups: Vec<GroupReport<W>>,
+}
+
+impl<W: Weight> FairnessReport<W> {
+    pub fn new<P: Preferences<W> + ?Sized>(num_items: usize, allocation: &[HashSet<usize>], groups: &[Vec<usize>], preferences: &P) -> Result<Self, MatchingError> {
+        Self::new_with(&SuccessiveShortestPathSolver, num_items, allocation, groups, preferences)
+    }
+
+    /// `new` with the bundle values found by the given solver. Fails when a group of
+    /// the allocation is missing or when `preferences` does not cover every member and
+    /// item.
+    pub fn new_with<P: Preferences<W> + ?Sized, S: MatchingSolver<W>>(solver: &S, num_items: usize, allocation: &[HashSet<usize>], groups: &[Vec<usize>], preferences: &P) -> Result<Self, MatchingError> {
+        let num_groups = allocation.len();
+        if groups.len() < num_groups {
+            return Err(MatchingError::MissingGroups { num_groups, found: groups.len() });
+        }
+        let value = |p: usize, bundle: &[usize]| bundle_value(solver, &groups[p], bundle, preferences);
+        let without = |bundle: &[usize], item: usize| bundle.iter().copied().filter(|&x| x != item).collect::<Vec<usize>>();
+        let bundles: Vec<Vec<usize>> = allocation
+            .iter()
+            .map(|bundle| {
+                let mut bundle: Vec<usize> = bundle.iter().copied().collect();
+                bundle.sort_unstable();
+                bundle
+            })
+            .collect();
+        let count = (0..num_groups).fold(W::zero(), |acc, _| acc + W::one());
+        let all_items: Vec<usize> = (0..num_items).collect();
+
+        let mut pairs = Vec::new();
+        let mut group_reports = Vec::new();
+        for p in 0..num_groups {
+            let own = &bundles[p];
+            let utility = value(p, own)?;
+            // The values of the own bundle without each of its items, for chores.
+            let own_without: Vec<W> = own.iter().map(|&item| value(p, &without(own, item))).collect::<Result<_, _>>()?;
+
+            for q in (0..num_groups).filter(|&q| q != p) {
+                let other = &bundles[q];
+                let other_value = value(p, other)?;
+                let other_without: Vec<W> = other.iter().map(|&item| value(p, &without(other, item))).collect::<Result<_, _>>()?;
+                let ef = !utility.definitely_lt(other_value);
+                let ef1 = ef
+                    || other_without.iter().any(|&rest| !utility.definitely_lt(rest))
+                    || own_without.iter().any(|&rest| !rest.definitely_lt(other_value));
+                let efx = ef || other_without.iter().all(|&rest| !utility.definitely_lt(rest));
+                let envy = if ef { W::zero() } else { other_value - utility };
+                pairs.push(PairReport { p, q, envy, ef, ef1, efx });
+            }
+
+            let whole = value(p, &all_items)?;
+            let prop = !(count * utility).definitely_lt(whole);
+            let mut prop1 = prop || own_without.iter().any(|&rest| !(count * rest).definitely_lt(whole));
+            for item in (0..num_items).filter(|item| !allocation[p].contains(item)) {
+                if prop1 {
+                    break;
+                }
+                let mut more = own.clone();
+                more.push(item);
+                prop1 = !(count * value(p, &more)?).definitely_lt(whole);
+            }
+            group_reports.push(GroupReport { p, utility, whole, prop, prop1 });
+        }
+        Ok(FairnessReport { pairs, groups: group_reports })
+    }
+
+    pub fn is_ef(&self) -> bool {
+        self.pairs.iter().all(|pair| pair.ef)
+    }
+
+    pub fn is_ef1(&self) -> bool {
+        self.pairs.iter().all(|pair| pair.ef1)
+    }
+
+    pub fn is_efx(&self) -> bool {
+        self.pairs.iter().all(|pair| pair.efx)
+    }
+
+    pub fn is_prop(&self) -> bool {
+        self.groups.iter().all(|group| group.prop)
+    }
+
+    pub fn is_prop1(&self) -> bool {
+        self.groups.iter().all(|group| group.prop1)
+    }
+
+    /// Largest envy between two groups.
+    pub fn max_envy(&self) -> W {
+        self.pairs.iter().fold(W::zero(), |acc, pair| if acc < pair.envy { pair.envy } else { acc })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::allocation::round_robin_allocation_by_group;
+    use rand::Rng;
+
+    #[test]
+    fn test_fairness_report() {
+        // Group 0 of one member holds item 2, group 1 of two members items 0 and 1.
+        let groups = vec![vec![0], vec![1, 2]];
+        let preferences: Vec<Vec<i64>> = vec![vec![5, 3, 1], vec![1, 1, 1], vec![1, 1, 1]];
+        let allocation = vec![HashSet::from([2]), HashSet::from([0, 1])];
+        let report = FairnessReport::new(3, &allocation, &groups, &preferences).unwrap();
+        // Group 0 only uses one item of the other bundle, worth 5, or 3 without item 0.
+        assert_eq!(report.pairs[0], PairReport { p: 0, q: 1, envy: 4, ef: false, ef1: false, efx: false });
+        assert_eq!(report.pairs[1], PairReport { p: 1, q: 0, envy: 0, ef: true, ef1: true, efx: true });
+        assert_eq!(report.groups[0], GroupReport { p: 0, utility: 1, whole: 5, prop: false, prop1: true });
+        assert_eq!(report.groups[1], GroupReport { p: 1, utility: 2, whole: 2, prop: true, prop1: true });
+        assert!(!report.is_ef1() && !report.is_prop() && report.is_prop1());
+        assert_eq!(report.max_envy(), 4);
+
+        // With item 1 instead group 0 still envies, but not beyond item 0.
+        let allocation = vec![HashSet::from([1]), HashSet::from([0, 2])];
+        let report = FairnessReport::new(3, &allocation, &groups, &preferences).unwrap();
+        assert_eq!(report.pairs[0], PairReport { p: 0, q: 1, envy: 2, ef: false, ef1: true, efx: false });
+
+        assert_eq!(FairnessReport::new(3, &allocation, &groups[..1], &preferences), Err(MatchingError::MissingGroups { num_groups: 2, found: 1 }));
+
+        // Groups whose members agree and fit their bundles value them additively, and
+        // round robin of goods is EF1 for them.
+        let mut rng = rand::thread_rng();
+        for _ in 0..20 {
+            let groups = vec![vec![0, 1, 2], vec![3, 4, 5]];
+            let mut preferences: Vec<Vec<i64>> = Vec::new();
+            for _ in 0..2 {
+                let row: Vec<i64> = (0..6).map(|_| rng.gen_range(0..10)).collect();
+                preferences.extend(std::iter::repeat_n(row, 3));
+            }
+            let (allocation, utility_list, _) = round_robin_allocation_by_group(6, 2, 3, &groups, &preferences).unwrap();
+            let report = FairnessReport::new(6, &allocation, &groups, &preferences).unwrap();
+            assert!(report.is_ef1());
+            assert_eq!(report.groups.iter().map(|group| group.utility).collect::<Vec<i64>>(), utility_list);
+        }
+    }
+}
@@ -0,0 +1,353 @@
+use crate::error::MatchingError;
+use crate::weight::Weight;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+
+/// Arc added to a `FlowNetwork`, to read its flow back.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ArcId {
+    node: usize,
+    index: usize,
+}
+
+/// Residual arc of a flow network: `cap` units are left of `capacity`, and `rev` is
+/// the position of the reverse arc in the adjacency list of `to`.
+#[derive(Clone, Debug)]
+pub(crate) struct Arc<W> {
+    pub(crate) to: usize,
+    pub(crate) cap: usize,
+    pub(crate) capacity: usize,
+    pub(crate) cost: W,
+    pub(crate) rev: usize,
+}
+
+/// Arc each node of a search was entered by, as `(node, index in its arcs)`.
+pub(crate) type Previous = Vec<Option<(usize, usize)>>;
+
+/// Heap entry of Dijkstra's algorithm, ordered so that `BinaryHeap` pops the
+/// smallest distance first.
+struct Candidate<W>(W, usize);
+
+impl<W: Weight> PartialEq for Candidate<W> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<W: Weight> Eq for Candidate<W> {}
+
+impl<W: Weight> PartialOrd for Candidate<W> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<W: Weight> Ord for Candidate<W> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.0.total_cmp(&self.0).then_with(|| other.1.cmp(&self.1))
+    }
+}
+
+/// Directed network with integer capacities and costs of type `W`, and a flow on it
+/// computed by successive shortest paths.
+///
+/// The Johnson potentials are kept between augmentations, so every augmenting path is
+/// found by Dijkstra on non-negative reduced costs; Bellman-Ford only runs once per
+/// call to `send` or `min_cost_flow`, which also lets arcs have negative costs. The
+/// matching code builds its networks with `add_arc` and sets the potentials itself.
+#[derive(Clone, Debug)]
+pub struct FlowNetwork<W> {
+    pub(crate) graph: Vec<Vec<Arc<W>>>,
+    pub(crate) potential: Vec<W>,
+    pub(crate) cost: W,
+}
+
+impl<W: Weight> FlowNetwork<W> {
+    /// Network of `nodes` nodes `0..nodes`, without arcs.
+    pub fn new(nodes: usize) -> Self {
+        FlowNetwork { graph: vec![Vec::new(); nodes], potential: vec![W::zero(); nodes], cost: W::zero() }
+    }
+
+    pub fn num_nodes(&self) -> usize {
+        self.graph.len()
+    }
+
+    /// Adds a node without arcs and returns it.
+    pub fn add_node(&mut self) -> usize {
+        self.graph.push(Vec::new());
+        self.potential.push(W::zero());
+        self.graph.len() - 1
+    }
+
+    /// Adds an arc from `from` to `to` carrying up to `capacity` units at `cost` each.
+    pub fn add_arc(&mut self, from: usize, to: usize, capacity: usize, cost: W) -> ArcId {
+        let rev_from = self.graph[to].len() + usize::from(from == to);
+        let rev_to = self.graph[from].len();
+        self.graph[from].push(Arc { to, cap: capacity, capacity, cost, rev: rev_from });
+        self.graph[to].push(Arc { to: from, cap: 0, capacity: 0, cost: -cost, rev: rev_to });
+        ArcId { node: from, index: rev_to }
+    }
+
+    /// Units of flow on `arc`.
+    pub fn flow(&self, arc: ArcId) -> usize {
+        let arc = &self.graph[arc.node][arc.index];
+        arc.capacity - arc.cap
+    }
+
+    /// Total cost of the flow.
+    pub fn cost(&self) -> W {
+        self.cost
+    }
+
+    /// Sends up to `amount` more units from `source` to `sink` at the smallest cost,
+    /// and returns how many units were sent, fewer when the network cannot carry
+    /// more.
+    ///
+    /// The flow on the network must be a minimum cost one, e.g. the empty flow when
+    /// no arc cost is negative or after earlier calls.
+    pub fn send(&mut self, source: usize, sink: usize, amount: usize) -> Result<usize, MatchingError> {
+        self.reset_potentials(source)?;
+        let mut sent = 0;
+        while sent < amount {
+            match self.augment(source, sink, amount - sent) {
+                Some((units, _)) => sent += units,
+                None => break,
+            }
+        }
+        Ok(sent)
+    }
+
+    /// Minimum cost flow where node `v` sends `supplies[v]` units, or takes
+    /// `-supplies[v]` units when it is negative, and returns its cost.
+    ///
+    /// The supplies of all nodes must add up to zero. When they cannot all be routed
+    /// the network keeps the largest flow it found and `InfeasibleSize` tells the
+    /// number of units routed.
+    pub fn min_cost_flow(&mut self, supplies: &[i64]) -> Result<W, MatchingError> {
+        let nodes = self.num_nodes();
+        if supplies.len() != nodes {
+            return Err(MatchingError::MissingSupplies { nodes, found: supplies.len() });
+        }
+        let excess: i64 = supplies.iter().sum();
+        if excess != 0 {
+            return Err(MatchingError::UnbalancedSupplies { excess });
+        }
+
+        // A super source feeds the supplies and a super sink drains the demands; their
+        // arcs come last in every adjacency list, so they are popped off afterwards.
+        let (source, sink) = (self.add_node(), self.add_node());
+        let mut requested = 0;
+        for (v, &supply) in supplies.iter().enumerate() {
+            match supply.cmp(&0) {
+                Ordering::Greater => {
+                    self.add_arc(source, v, supply as usize, W::zero());
+                    requested += supply as usize;
+                }
+                Ordering::Less => {
+                    self.add_arc(v, sink, supply.unsigned_abs() as usize, W::zero());
+                }
+                Ordering::Equal => {}
+            }
+        }
+        let sent = self.send(source, sink, requested);
+        for (v, &supply) in supplies.iter().enumerate() {
+            if supply != 0 {
+                self.graph[v].pop();
+            }
+        }
+        self.graph.truncate(nodes);
+        self.potential.truncate(nodes);
+
+        let sent = sent?;
+        if sent < requested {
+            return Err(MatchingError::InfeasibleSize { requested, largest: sent });
+        }
+        Ok(self.cost)
+    }
+
+    /// Potentials of the shortest residual distances from `source` by Bellman-Ford,
+    /// zero for the nodes it cannot reach, which no augmenting path visits.
+    fn reset_potentials(&mut self, source: usize) -> Result<(), MatchingError> {
+        let nodes = self.num_nodes();
+        let mut distance: Vec<Option<W>> = vec![None; nodes];
+        distance[source] = Some(W::zero());
+        for round in 0..=nodes {
+            let mut relaxed = false;
+            for u in 0..nodes {
+                let Some(d) = distance[u] else { continue };
+                for arc in self.graph[u].iter().filter(|arc| arc.cap > 0) {
+                    let next = d + arc.cost;
+                    if distance[arc.to].is_none_or(|best| next.definitely_lt(best)) {
+                        distance[arc.to] = Some(next);
+                        relaxed = true;
+                    }
+                }
+            }
+            if !relaxed {
+                break;
+            }
+            if round == nodes {
+                return Err(MatchingError::NegativeCycle);
+            }
+        }
+        self.potential = distance.into_iter().map(|d| d.unwrap_or_else(W::zero)).collect();
+        Ok(())
+    }
+
+    /// Reduced cost of residual arc `idx` of node `u`.
+    pub(crate) fn reduced_cost(&self, u: usize, idx: usize) -> W {
+        let arc = &self.graph[u][idx];
+        arc.cost + self.potential[u] - self.potential[arc.to]
+    }
+
+    /// Dijkstra from node `from` on the reduced costs, with the arc each reached node
+    /// was entered by.
+    pub(crate) fn shortest_paths(&self, from: usize) -> (Vec<Option<W>>, Previous) {
+        let nodes = self.graph.len();
+        let mut distance: Vec<Option<W>> = vec![None; nodes];
+        let mut previous: Previous = vec![None; nodes];
+        let mut heap = BinaryHeap::new();
+        distance[from] = Some(W::zero());
+        heap.push(Candidate(W::zero(), from));
+
+        while let Some(Candidate(d, u)) = heap.pop() {
+            if distance[u].is_some_and(|best| best.definitely_lt(d)) {
+                continue;
+            }
+            for (idx, arc) in self.graph[u].iter().enumerate() {
+                if arc.cap == 0 {
+                    continue;
+                }
+                // Rounding may leave a reduced cost marginally below zero.
+                let reduced = self.reduced_cost(u, idx);
+                let next = if reduced < W::zero() { d } else { d + reduced };
+                if distance[arc.to].is_none_or(|best| next.definitely_lt(best)) {
+                    distance[arc.to] = Some(next);
+                    previous[arc.to] = Some((u, idx));
+                    heap.push(Candidate(next, arc.to));
+                }
+            }
+        }
+        (distance, previous)
+    }
+
+    /// Costs of the shortest residual paths from node `from` to every node, `None`
+    /// for the unreachable ones.
+    pub(crate) fn distances(&self, from: usize) -> Vec<Option<W>> {
+        let (distance, _) = self.shortest_paths(from);
+        distance
+            .into_iter()
+            .zip(&self.potential)
+            .map(|(d, &p)| d.map(|d| d - self.potential[from] + p))
+            .collect()
+    }
+
+    /// Sends up to `limit` units along a shortest path from `source` to `sink`.
+    ///
+    /// Returns the number of units sent and the node the path enters the sink from,
+    /// or `None` when the sink is no longer reachable.
+    pub(crate) fn augment(&mut self, source: usize, sink: usize, limit: usize) -> Option<(usize, usize)> {
+        let (distance, previous) = self.shortest_paths(source);
+
+        // Capping the distances at the sink distance keeps the reduced costs of
+        // arcs leaving unreached nodes non-negative.
+        let reach = distance[sink]?;
+        for (p, d) in self.potential.iter_mut().zip(&distance) {
+            *p = *p + match d {
+                Some(d) if *d < reach => *d,
+                _ => reach,
+            };
+        }
+
+        let (last, _) = previous[sink]?;
+        let mut path = Vec::new();
+        let mut v = sink;
+        while let Some((u, idx)) = previous[v] {
+            path.push((u, idx));
+            v = u;
+        }
+        let units = path.iter().fold(limit, |units, &(u, idx)| units.min(self.graph[u][idx].cap));
+        for (u, idx) in path {
+            self.push(u, idx, units);
+        }
+        Some((units, last))
+    }
+
+    /// Pushes `units` units along residual arc `idx` of node `u`.
+    pub(crate) fn push(&mut self, u: usize, idx: usize, units: usize) {
+        let (to, rev, cost) = (self.graph[u][idx].to, self.graph[u][idx].rev, self.graph[u][idx].cost);
+        self.graph[u][idx].cap -= units;
+        self.graph[to][rev].cap += units;
+        self.cost = self.cost + times(cost, units);
+    }
+
+    /// Whether residual arc `idx` of node `u` has a zero reduced cost. Flow pushed
+    /// around a cycle of such arcs keeps both the cost and the potentials.
+    pub(crate) fn is_tight(&self, u: usize, idx: usize) -> bool {
+        self.graph[u][idx].cap > 0 && !W::zero().definitely_lt(self.reduced_cost(u, idx))
+    }
+
+    /// Puts every arc back to its full capacity.
+    pub(crate) fn clear_flow(&mut self) {
+        for arcs in self.graph.iter_mut() {
+            for arc in arcs.iter_mut() {
+                arc.cap = arc.capacity;
+            }
+        }
+        self.cost = W::zero();
+    }
+}
+
+// w added n times, by doubling
+fn times<W: Weight>(w: W, n: usize) -> W {
+    let (mut total, mut power, mut n) = (W::zero(), w, n);
+    while n > 0 {
+        if n & 1 == 1 {
+            total = total + power;
+        }
+        power = power + power;
+        n >>= 1;
+    }
+    total
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_min_cost_flow() {
+        // Two units from 0 to 3, through 1 at cost 1 + 1 or through 2 at cost 2 + 2,
+        // and one unit only fits through 1.
+        let mut network: FlowNetwork<i64> = FlowNetwork::new(4);
+        let a = network.add_arc(0, 1, 1, 1);
+        let b = network.add_arc(0, 2, 2, 2);
+        network.add_arc(1, 3, 2, 1);
+        network.add_arc(2, 3, 2, 2);
+        assert_eq!(network.min_cost_flow(&[2, 0, 0, -2]), Ok(6));
+        assert_eq!((network.flow(a), network.flow(b)), (1, 1));
+
+        // A third unit does not fit.
+        let mut network: FlowNetwork<i64> = FlowNetwork::new(3);
+        network.add_arc(0, 1, 2, 0);
+        network.add_arc(1, 2, 5, 0);
+        assert_eq!(network.min_cost_flow(&[3, 0, -3]), Err(MatchingError::InfeasibleSize { requested: 3, largest: 2 }));
+        assert_eq!(network.num_nodes(), 3);
+        assert_eq!(network.min_cost_flow(&[1, 0]), Err(MatchingError::MissingSupplies { nodes: 3, found: 2 }));
+        assert_eq!(network.min_cost_flow(&[1, 0, 0]), Err(MatchingError::UnbalancedSupplies { excess: 1 }));
+
+        // Negative costs are fine, negative cycles are not.
+        let mut network: FlowNetwork<i64> = FlowNetwork::new(3);
+        network.add_arc(0, 1, 3, -4);
+        network.add_arc(1, 2, 1, 1);
+        network.add_arc(0, 2, 3, -1);
+        assert_eq!(network.send(0, 2, 5), Ok(4));
+        assert_eq!(network.cost(), -6);
+        let mut network: FlowNetwork<i64> = FlowNetwork::new(2);
+        network.add_arc(0, 1, 1, -1);
+        network.add_arc(1, 0, 1, -1);
+        assert_eq!(network.send(0, 1, 1), Err(MatchingError::NegativeCycle));
+    }
+}
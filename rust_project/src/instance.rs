@@ -0,0 +1,95 @@
+use crate::config::Valuations;
+use rand::Rng;
+
+
+/// Random instance of the experiments: `num_groups` groups of `n_each` consecutive
+/// agents, and preferences of `num_agents` agents for `num_items` items drawn
+/// uniformly with the sign given by `valuations`, or zero or one.
+pub fn create_groups_pref(n_each: usize, num_groups: usize, num_agents: usize, num_items: usize, valuations: Valuations) -> (Vec<Vec<usize>>, Vec<Vec<f64>>) {
+    let groups = consecutive_groups(&vec![n_each; num_groups]);
+    (groups, create_pref(num_agents, num_items, valuations))
+}
+
+/// `create_groups_pref` for groups of the given sizes, group `p` taking the
+/// `sizes[p]` agents after those of the groups before it, and one agent per member.
+pub fn create_groups_pref_with_sizes(sizes: &[usize], num_items: usize, valuations: Valuations) -> (Vec<Vec<usize>>, Vec<Vec<f64>>) {
+    let groups = consecutive_groups(sizes);
+    (groups, create_pref(sizes.iter().sum(), num_items, valuations))
+}
+
+/// Preference of the perturbed instances, made worse so that a group can only lose
+/// utility: a good loses its value, a chore becomes as bad as chores are drawn, and
+/// a preference of mixed valuations does whichever its sign calls for.
+pub fn worsen_preference(preference: f64, valuations: Valuations) -> f64 {
+    match valuations {
+        Valuations::Goods | Valuations::Binary => 0.0,
+        Valuations::Chores => -1.0,
+        Valuations::Mixed => if preference > 0.0 { 0.0 } else { -1.0 },
+    }
+}
+
+/// Squared loss from `num_1` down to `num_2`, which only depends on their
+/// difference, so it reads the same for utilities of goods, chores and mixed bundles.
+pub fn calculate_diff(num_1: f64, num_2: f64) -> f64 {
+    if num_1 >= num_2 {
+        (num_1 - num_2).powi(2)
+    }
+    else {
+        0.0
+    }
+}
+
+fn consecutive_groups(sizes: &[usize]) -> Vec<Vec<usize>> {
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut first = 0;
+    for &size in sizes {
+        groups.push((first..first + size).collect());
+        first += size;
+    }
+    groups
+}
+
+fn create_pref(num_agents: usize, num_items: usize, valuations: Valuations) -> Vec<Vec<f64>> {
+    let mut preferences: Vec<Vec<f64>> = Vec::new();
+    let mut rng = rand::thread_rng();
+    for _ in 0..num_agents {
+        preferences.push((0..num_items).map(|_| match valuations {
+            Valuations::Goods => rng.gen(),
+            Valuations::Chores => -rng.gen::<f64>(),
+            Valuations::Mixed => rng.gen_range(-1.0..1.0),
+            Valuations::Binary => if rng.gen() { 1.0 } else { 0.0 },
+        }).collect());
+    }
+    preferences
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::allocation::round_robin_allocation_by_group;
+
+    #[test]
+    fn test_worsen_preference() {
+        // Group 0 takes item 0, its lighter chore, until that chore gets worse.
+        let groups = vec![vec![0], vec![1]];
+        let preferences: Vec<Vec<f64>> = vec![vec![-0.2, -0.5], vec![-0.3, -0.4]];
+        let (_, utility_list, _) = round_robin_allocation_by_group(2, 2, 1, &groups, &preferences).unwrap();
+        assert_eq!(utility_list, vec![-0.2, -0.4]);
+
+        let mut another_preferences = preferences.clone();
+        another_preferences[0][0] = worsen_preference(preferences[0][0], Valuations::Chores);
+        let (_, another_utility_list, _) = round_robin_allocation_by_group::<f64, _>(2, 2, 1, &groups, &another_preferences).unwrap();
+        assert_eq!(another_utility_list[0], -0.5);
+        assert!((calculate_diff(utility_list[0], another_utility_list[0]) - 0.09).abs() < 1e-9);
+
+        // Setting the chore to zero would improve it and hide the change.
+        another_preferences[0][0] = 0.0;
+        let (_, another_utility_list, _) = round_robin_allocation_by_group::<f64, _>(2, 2, 1, &groups, &another_preferences).unwrap();
+        assert_eq!(calculate_diff(utility_list[0], another_utility_list[0]), 0.0);
+
+        assert_eq!(worsen_preference(0.7, Valuations::Goods), 0.0);
+        assert_eq!(worsen_preference(0.7, Valuations::Mixed), 0.0);
+        assert_eq!(worsen_preference(-0.7, Valuations::Mixed), -1.0);
+    }
+}
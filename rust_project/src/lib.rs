@@ -0,0 +1,15 @@
+pub mod config;
+pub mod error;
+pub mod fairness;
+pub mod flow;
+pub mod instance;
+pub mod allocation;
+mod auction;
+mod binary;
+pub mod matching;
+pub mod picking;
+pub mod preferences;
+pub mod solver;
+pub mod tie_break;
+pub mod welfare;
+pub mod weight;
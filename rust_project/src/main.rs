@@ -1,7 +1,3 @@
-use std::collections::HashSet;
-use std::collections::HashMap;
-use rand::Rng;
-use csv;
 use std::fs;
 use std::fs::File;
 use csv::Writer;
@@ -9,17 +5,19 @@ use std::env;
 use indicatif::{ProgressBar};
 use std::process;
 
-mod config;
-mod allocation;
-mod matching;
+use rust_project::config::{Allocation, Config, Ties};
+use rust_project::error::MatchingError;
+use rust_project::fairness::FairnessReport;
+use rust_project::instance::{calculate_diff, create_groups_pref_with_sizes, worsen_preference};
+use rust_project::allocation::{envy_cycle_allocation_with_quotas, round_robin_allocation_with_quotas, yankee_swap_allocation_with_quotas};
+use rust_project::matching::{compute_max_weight_matching_with, compute_max_weight_sensitivity};
+use rust_project::tie_break::{global_demand, TieBreak};
+use rust_project::weight::Weight;
+use rust_project::welfare::{max_nash_welfare_allocation_with, nash_welfare_local_search_with};
 
-use crate::config::Config;
-use crate::allocation::round_robin_allocation_by_group;
-use crate::matching::compute_max_weight_matching;
 
 
-
-fn create_wtr(n_each: usize, num_groups: usize, num_items: usize) -> (Writer<File>, Writer<File>, Writer<File>, Writer<File>){
+fn create_wtr(n_each: &str, num_groups: usize, num_items: usize) -> (Writer<File>, Writer<File>, Writer<File>, Writer<File>){
     let make_file = |str: String| -> Writer<File>{
         let file_name = format!(
             "outcome/outcome_{}_{}_{}_{}.csv",
@@ -31,10 +29,10 @@ fn create_wtr(n_each: usize, num_groups: usize, num_items: usize) -> (Writer<Fil
         let file_out = fs::File::options()
             .write(true)
             .create(true)
+            .truncate(true)
             .open(file_name)
             .expect("CSV write failure");
-        let wtr = csv::Writer::from_writer(file_out);
-        wtr
+        csv::Writer::from_writer(file_out)
     };
     let wtr_p = make_file("p".to_string());
     let wtr_pq = make_file("pq".to_string());
@@ -43,32 +41,6 @@ fn create_wtr(n_each: usize, num_groups: usize, num_items: usize) -> (Writer<Fil
     (wtr_p, wtr_pq, wtr_diff_p, wtr_diff_pq)
 }
 
-fn create_groups_pref(n_each: usize, num_groups: usize, num_agents: usize, num_items: usize) -> (Vec<Vec<usize>>, Vec<Vec<f64>>) {
-
-    let mut groups: Vec<Vec<usize>> = Vec::new();
-    for i in 0..num_groups {
-        groups.push((0..n_each).map(|j| i * n_each + j).collect());
-    }
-
-    let mut preferences: Vec<Vec<f64>> = Vec::new();
-    let mut rng = rand::thread_rng();
-    for _ in 0..num_agents {
-        preferences.push((0..num_items).map(|_| rng.gen()).collect());
-    }
-
-    (groups, preferences)
-}
-
-
-fn calculate_diff (num_1:f64, num_2:f64) -> f64 {
-    if num_1 >= num_2 {
-        (num_1 - num_2).powi(2)
-    }
-    else {
-        0.0
-    }
-}
-
 fn main() {
     let args: Vec<String> = env::args().collect();
     let config = Config::new(&args).unwrap_or_else(|err| {
@@ -78,57 +50,146 @@ fn main() {
     //let n_each: usize = 10;
     //let num_groups: usize = 4;
     //let num_items: usize = 100;
-    let num_agents = config.n_each * config.num_groups;
+    let num_agents: usize = config.group_sizes.iter().sum();
     let num_tries: usize = 100;
 
-    let (mut wtr_p, mut wtr_pq, mut wtr_diff_p, mut wtr_diff_pq) = create_wtr(config.n_each, config.num_groups, config.num_items);
+    // Equal groups keep the names of the files from before groups could differ.
+    let sizes_label = if config.group_sizes.windows(2).all(|pair| pair[0] == pair[1]) {
+        config.group_sizes.first().map_or(0, |&size| size).to_string()
+    } else {
+        config.group_sizes.iter().map(usize::to_string).collect::<Vec<String>>().join("-")
+    };
+    let (mut wtr_p, mut wtr_pq, mut wtr_diff_p, mut wtr_diff_pq) = create_wtr(&sizes_label, config.num_groups, config.num_items);
 
     let pb = ProgressBar::new(num_tries as u64);
     
-    for _ in 0..num_tries {
+    // A bad instance is skipped instead of aborting the whole experiment.
+    let skip = |err: MatchingError| println!("Skipping instance: {}", err);
+
+    'tries: for _ in 0..num_tries {
         //thread::sleep(Duration::from_millis(5));
         pb.inc(1);
 
-        let (groups, preferences) = create_groups_pref(
-            config.n_each, 
-            config.num_groups,
-            num_agents, 
+        let (groups, preferences) = create_groups_pref_with_sizes(
+            &config.group_sizes,
             config.num_items,
+            config.valuations,
         );
         //println!("groups: {:?}", groups);
 
-        let (allocation, utility_list) = round_robin_allocation_by_group(
-            config.num_items, 
-            config.num_groups,
-            config.n_each, 
-            &groups, 
-            &preferences,
-        );
+        let tie_break = match config.ties {
+            Ties::Solver => TieBreak::Solver,
+            Ties::Smallest => TieBreak::SmallestItems,
+            Ties::Demand => match global_demand(&(0..num_agents).collect::<Vec<usize>>(), config.num_items, &preferences) {
+                Ok(demand) => TieBreak::LowestDemand(demand),
+                Err(err) => {
+                    skip(err);
+                    continue 'tries;
+                }
+            },
+            Ties::Random(seed) => TieBreak::Random(seed),
+        };
+
+        // Allocation of the instance, or of the instance with one preference changed.
+        let allocate = |preferences: &Vec<Vec<f64>>| match config.allocation {
+            Allocation::RoundRobin => round_robin_allocation_with_quotas(
+                &config.solver,
+                &tie_break,
+                &config.sequence,
+                config.num_items,
+                &config.quotas,
+                &groups,
+                preferences,
+            ),
+            Allocation::YankeeSwap => yankee_swap_allocation_with_quotas(config.num_items, &config.quotas, &groups, preferences),
+            Allocation::EnvyCycle => envy_cycle_allocation_with_quotas(&config.solver, config.num_items, &config.quotas, &groups, preferences),
+            // The assignments of the members are not used below.
+            Allocation::MaxNashWelfare => max_nash_welfare_allocation_with(&config.solver, config.num_items, &config.quotas, &groups, preferences)
+                .map(|(allocation, _, utility_list)| (allocation, utility_list, Vec::new())),
+            Allocation::NashLocalSearch => nash_welfare_local_search_with(&config.solver, config.num_items, &config.quotas, &groups, preferences)
+                .map(|(allocation, _, utility_list)| (allocation, utility_list, Vec::new())),
+        };
+
+        let (allocation, utility_list, _) = match allocate(&preferences) {
+            Ok(outcome) => outcome,
+            Err(err) => {
+                skip(err);
+                continue 'tries;
+            }
+        };
         // println!("allocation \n {:?}", allocation);
         // println!("utility_list \n {:?}", utility_list);
 
 
+        // Group 0's value for group 1's bundle, and how it changes with one preference,
+        // which answers the perturbed instances that leave that bundle as it is.
+        let mut sensitivity = match compute_max_weight_sensitivity(
+            groups[0].clone(),
+            allocation[1].iter().copied().collect(),
+            &preferences,
+            groups[0].len().min(allocation[1].len()),
+        ) {
+            Ok(sensitivity) => sensitivity,
+            Err(err) => {
+                skip(err);
+                continue 'tries;
+            }
+        };
+        let max_weight = sensitivity.weight();
+
+        // Whether agent `i`'s group values its bundle as before once its preference for
+        // item `j` changes.
+        let still_optimal = |i: usize, j: usize, another_preferences: &Vec<Vec<f64>>| -> Result<bool, MatchingError> {
+            let Some(p) = groups.iter().position(|group| group.contains(&i)) else {
+                return Ok(true);
+            };
+            if !allocation[p].contains(&j) {
+                return Ok(true);
+            }
+            let (another_utility, _) = compute_max_weight_matching_with(
+                &config.solver,
+                groups[p].clone(),
+                allocation[p].iter().copied().collect(),
+                another_preferences,
+                groups[p].len().min(allocation[p].len()),
+            )?;
+            Ok(!another_utility.definitely_lt(utility_list[p]))
+        };
+
         let mut difference_p = 0.0;
         let mut difference_pq = 0.0;
         let mut count = 0;
         for i in 0..num_agents {
             for j in 0..config.num_items {
 
-                let mut another_preferences: Vec<Vec<f64>> = vec![Vec::new(); num_agents];
-
-                for k in 0..num_agents {
-                    another_preferences[k] = preferences[k].clone();
-                }
+                let mut another_preferences: Vec<Vec<f64>> = preferences.clone();
                 // println!("----{}, {}----",i,j);
-                another_preferences[i][j] = 0.0;
+                another_preferences[i][j] = worsen_preference(preferences[i][j], config.valuations);
 
-                let (another_allocation, another_utility_list) = round_robin_allocation_by_group(
-                    config.num_items, 
-                    config.num_groups,
-                    config.n_each, 
-                    &groups, 
-                    &another_preferences,
-                );
+                // A worse preference lowers no allocation's welfare, so the maximum Nash
+                // welfare stays where it was if its own does not drop, and is only solved
+                // again when it does.
+                let kept = match config.allocation {
+                    Allocation::MaxNashWelfare => match still_optimal(i, j, &another_preferences) {
+                        Ok(kept) => kept,
+                        Err(err) => {
+                            skip(err);
+                            continue 'tries;
+                        }
+                    },
+                    _ => false,
+                };
+                let (another_allocation, another_utility_list) = if kept {
+                    (allocation.clone(), utility_list.clone())
+                } else {
+                    match allocate(&another_preferences) {
+                        Ok((another_allocation, another_utility_list, _)) => (another_allocation, another_utility_list),
+                        Err(err) => {
+                            skip(err);
+                            continue 'tries;
+                        }
+                    }
+                };
 
                 // println!("another_allocation {:?}",another_allocation);
 
@@ -142,19 +203,24 @@ fn main() {
     
                     //for q in 0..config.num_groups {
                         //if q!=1 {continue;}
-                let (max_weight, assign) = compute_max_weight_matching(
-                    groups[0].clone(), 
-                    allocation[1].clone().into_iter().collect(), 
-                    &preferences, 
-                    config.n_each,
-                );
-
-                let (another_max_weight, another_assign) = compute_max_weight_matching(
-                    groups[0].clone(), 
-                    another_allocation[1].clone().into_iter().collect(), 
-                    &another_preferences, 
-                    config.n_each,
-                );
+                let another_max_weight = if another_allocation[1] == allocation[1] {
+                    // Agents outside group 0 or items outside the bundle leave the value as it is.
+                    sensitivity.weight_if(i, j, another_preferences[i][j]).unwrap_or(max_weight)
+                } else {
+                    match compute_max_weight_matching_with(
+                        &config.solver,
+                        groups[0].clone(), 
+                        another_allocation[1].clone().into_iter().collect(), 
+                        &another_preferences, 
+                        groups[0].len().min(another_allocation[1].len()),
+                    ) {
+                        Ok((another_max_weight, _)) => another_max_weight,
+                        Err(err) => {
+                            skip(err);
+                            continue 'tries;
+                        }
+                    }
+                };
                 let each_diff_pq =  calculate_diff(max_weight, another_max_weight);
                 difference_pq += each_diff_pq;
                         //println!("each_diff of {} toward {} : {}", p, q, each_diff_pq);
@@ -169,6 +235,53 @@ fn main() {
                 }
             }
         }
+
+        // Every step that may skip the instance comes before the first row is written,
+        // so that the files stay aligned.
+        let report = match FairnessReport::new_with(&config.solver, config.num_items, &allocation, &groups, &preferences) {
+            Ok(report) => report,
+            Err(err) => {
+                skip(err);
+                continue 'tries;
+            }
+        };
+
+        // Nash welfare of the allocation against a local maximum as the benchmark.
+        let benchmark = if config.nash_benchmark {
+            match nash_welfare_local_search_with(&config.solver, config.num_items, &config.quotas, &groups, &preferences) {
+                Ok((_, benchmark, _)) => Some(benchmark),
+                Err(err) => {
+                    skip(err);
+                    continue 'tries;
+                }
+            }
+        } else {
+            None
+        };
+
+        let mut bundle_values: Vec<Vec<f64>> = Vec::with_capacity(config.num_groups);
+        for group in groups.iter().take(config.num_groups) {
+            let mut values = Vec::with_capacity(allocation.len());
+            for bundle_q in &allocation {
+                // println!("{:?}",bundle_q);
+                let (max_weight, _) = match compute_max_weight_matching_with(
+                    &config.solver,
+                    group.clone(), 
+                    bundle_q.iter().copied().collect(), 
+                    &preferences, 
+                    group.len().min(bundle_q.len()),
+                ) {
+                    Ok(outcome) => outcome,
+                    Err(err) => {
+                        skip(err);
+                        continue 'tries;
+                    }
+                };
+                values.push(max_weight);
+            }
+            bundle_values.push(values);
+        }
+
         println!("count : {}", count);
         // if count > num_agents{
         //     break;
@@ -177,21 +290,27 @@ fn main() {
         println!("difference_p, difference_pq : {}, {}", difference_p, difference_pq);
         wtr_diff_p.serialize(difference_p).expect("CSV write failure");
         wtr_diff_pq.serialize(difference_pq).expect("CSV write failure");
-        
+
+        println!(
+            "EF: {}, EF1: {}, EFX: {}, PROP: {}, PROP1: {}, max envy: {}",
+            report.is_ef(),
+            report.is_ef1(),
+            report.is_efx(),
+            report.is_prop(),
+            report.is_prop1(),
+            report.max_envy(),
+        );
+        if let Some(benchmark) = benchmark {
+            let nash_product: f64 = utility_list.iter().product();
+            println!("Nash product: {}, local search: {}", nash_product, benchmark);
+        }
+
         for p in 0..config.num_groups {
             // println!("----------Class {} evaluates class {} 's bundle as {}.", p, p, utility_list[p]);
             //// println!("----------Class {} evaluates whole set of item as {}.", p, compute_max_weight_matching(groups_util[p].clone(), (0..num_items).collect(), &preferences).0);
             wtr_p.serialize(utility_list[p]).expect("CSV write failure");
 
-            for q in 0..config.num_groups {
-                // println!("{:?}",bundle_q);
-                let (max_weight, assign) = compute_max_weight_matching(
-                    groups[p].clone(), 
-                    allocation[q].clone().into_iter().collect(), 
-                    &preferences, 
-                    config.n_each,
-                );
-
+            for (q, &max_weight) in bundle_values[p].iter().enumerate() {
                 println!("Class {} evaluates class {}'s bundle as {}.", p, q, max_weight);
 
                 if q == (p + 1)%config.num_groups {
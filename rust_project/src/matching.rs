@@ -1,162 +1,1136 @@
+use crate::error::MatchingError;
+use crate::flow::{FlowNetwork, Previous};
+use crate::preferences::Preferences;
+use crate::solver::{MatchingSolver, SuccessiveShortestPathSolver};
+use crate::tie_break::TieBreak;
+use crate::weight::Weight;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
 use std::collections::HashSet;
 use std::collections::HashMap;
 
 
-pub fn compute_max_weight_matching(left_list: Vec<usize>, right_list: Vec<usize>, preferences: &[Vec<f64>], match_size: usize) -> (f64, HashSet<usize>) {
-    let mut weights: Vec<Vec<i128>> = vec![vec![0; right_list.len()]; left_list.len()];
-    let mut right_map: HashMap<usize, usize> = HashMap::new();
+/// Item and value each matched agent receives, keyed by agent id.
+pub type Assignment<W> = HashMap<usize, (usize, W)>;
 
+/// Items and values each matched agent receives, in increasing item order, keyed by
+/// agent id, for agents that may use several items.
+pub type MultiAssignment<W> = HashMap<usize, Vec<(usize, W)>>;
+
+/// Optimal solution of the dual of the matching LP
+///
+/// ```text
+/// max  sum w_e x_e
+/// s.t. sum of x_e at agent i   <= c_i     (dual u_i >= 0)
+///      sum of x_e at item j    <= b_j     (dual v_j >= 0)
+///      sum x_e                  = k       (dual lambda, free)
+///      x_e                     <= 1       (dual z_e >= 0)
+/// ```
+///
+/// with one unit per agent and item for a plain matching, in which case every `z_e`
+/// is zero. Every edge has `u_i + v_j + lambda + z_e >= w_e`, with equality on the
+/// matched edges, so the dual bounds the weight of every matching of size `k` by the
+/// weight of the matched one. Agents, items and edges left out have a dual of zero.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Certificate<W> {
+    pub agent_duals: HashMap<usize, W>,
+    pub item_duals: HashMap<usize, W>,
+    pub size_dual: W,
+    pub edge_duals: HashMap<(usize, usize), W>,
+}
+
+pub fn compute_max_weight_matching<W: Weight, P: Preferences<W> + ?Sized>(left_list: Vec<usize>, right_list: Vec<usize>, preferences: &P, match_size: usize) -> Result<(W, HashSet<usize>), MatchingError> {
+    compute_max_weight_matching_with(&SuccessiveShortestPathSolver, left_list, right_list, preferences, match_size)
+}
+
+/// Same as `compute_max_weight_matching`, but tells which agent gets which item.
+pub fn compute_max_weight_assignment<W: Weight, P: Preferences<W> + ?Sized>(left_list: Vec<usize>, right_list: Vec<usize>, preferences: &P, match_size: usize) -> Result<(W, Assignment<W>), MatchingError> {
+    compute_max_weight_assignment_with(&SuccessiveShortestPathSolver, left_list, right_list, preferences, match_size)
+}
+
+/// `compute_max_weight_matching` solved by the given solver.
+pub fn compute_max_weight_matching_with<W: Weight, P: Preferences<W> + ?Sized, S: MatchingSolver<W>>(solver: &S, left_list: Vec<usize>, right_list: Vec<usize>, preferences: &P, match_size: usize) -> Result<(W, HashSet<usize>), MatchingError> {
+    let (max_weight, assignment) = compute_max_weight_assignment_with(solver, left_list, right_list, preferences, match_size)?;
+    Ok((max_weight, assignment.values().map(|&(item, _)| item).collect()))
+}
+
+/// `compute_max_weight_assignment` solved by the given solver.
+///
+/// Only the edges of `preferences` can be matched, so with sparse preferences the
+/// matching may be smaller than `match_size`. Fails when `match_size` exceeds the
+/// number of agents or items, or when `preferences` does not cover them.
+pub fn compute_max_weight_assignment_with<W: Weight, P: Preferences<W> + ?Sized, S: MatchingSolver<W>>(solver: &S, left_list: Vec<usize>, right_list: Vec<usize>, preferences: &P, match_size: usize) -> Result<(W, Assignment<W>), MatchingError> {
     // println!("left_list,right_list {:?},{:?}",left_list,right_list);
+    preferences.validate(&left_list, &right_list)?;
+
+    let columns: HashMap<usize, usize> = right_list.iter().enumerate().map(|(j, &right)| (right, j)).collect();
+    let rows: Vec<Vec<(usize, W)>> = left_list.iter().map(|&left| preferences.edges(left, &columns)).collect();
+
+    let (_, matched_pairs) = solver.solve_sparse(&rows, right_list.len(), match_size)?;
+    assignment_from_pairs(&rows, &left_list, &right_list, matched_pairs)
+}
+
+/// Best matching of at most `max_size` pairs, whatever the signs of the preferences.
+///
+/// Unlike `compute_max_weight_matching`, which matches exactly `match_size` pairs, a
+/// member stays unmatched whenever every item left to them would lower the weight,
+/// e.g. when all of them are chores. Among equally good matchings the smallest one is
+/// returned.
+pub fn compute_max_weight_matching_up_to<W: Weight, P: Preferences<W> + ?Sized>(left_list: Vec<usize>, right_list: Vec<usize>, preferences: &P, max_size: usize) -> Result<(W, HashSet<usize>), MatchingError> {
+    compute_max_weight_matching_up_to_with(&SuccessiveShortestPathSolver, left_list, right_list, preferences, max_size)
+}
+
+/// Same as `compute_max_weight_matching_up_to`, but tells which agent gets which item.
+pub fn compute_max_weight_assignment_up_to<W: Weight, P: Preferences<W> + ?Sized>(left_list: Vec<usize>, right_list: Vec<usize>, preferences: &P, max_size: usize) -> Result<(W, Assignment<W>), MatchingError> {
+    compute_max_weight_assignment_up_to_with(&SuccessiveShortestPathSolver, left_list, right_list, preferences, max_size)
+}
+
+/// `compute_max_weight_matching_up_to` solved by the given solver.
+pub fn compute_max_weight_matching_up_to_with<W: Weight, P: Preferences<W> + ?Sized, S: MatchingSolver<W>>(solver: &S, left_list: Vec<usize>, right_list: Vec<usize>, preferences: &P, max_size: usize) -> Result<(W, HashSet<usize>), MatchingError> {
+    let (max_weight, assignment) = compute_max_weight_assignment_up_to_with(solver, left_list, right_list, preferences, max_size)?;
+    Ok((max_weight, assignment.values().map(|&(item, _)| item).collect()))
+}
+
+/// `compute_max_weight_assignment_up_to` solved by the given solver.
+pub fn compute_max_weight_assignment_up_to_with<W: Weight, P: Preferences<W> + ?Sized, S: MatchingSolver<W>>(solver: &S, left_list: Vec<usize>, right_list: Vec<usize>, preferences: &P, max_size: usize) -> Result<(W, Assignment<W>), MatchingError> {
+    preferences.validate(&left_list, &right_list)?;
+
+    let columns: HashMap<usize, usize> = right_list.iter().enumerate().map(|(j, &right)| (right, j)).collect();
+    let rows: Vec<Vec<(usize, W)>> = left_list.iter().map(|&left| preferences.edges(left, &columns)).collect();
+
+    // The curve starts with the empty matching, so a matching only grows while that
+    // strictly pays off.
+    let (_, matched_pairs) = solver
+        .solve_curve(&rows, right_list.len(), max_size)?
+        .into_iter()
+        .fold((W::zero(), Vec::new()), |best, entry| if best.0.definitely_lt(entry.0) { entry } else { best });
+    assignment_from_pairs(&rows, &left_list, &right_list, matched_pairs)
+}
+
+/// Maximum weight matching of size `match_size` in which item `item` has `units[item]`
+/// identical units, e.g. the seats of a course, so that up to that many agents may
+/// share it. Each agent still receives at most one item.
+pub fn compute_max_weight_b_matching<W: Weight, P: Preferences<W> + ?Sized>(left_list: Vec<usize>, right_list: Vec<usize>, units: &[usize], preferences: &P, match_size: usize) -> Result<(W, Assignment<W>), MatchingError> {
+    compute_max_weight_b_matching_with(&SuccessiveShortestPathSolver, left_list, right_list, units, preferences, match_size)
+}
+
+/// `compute_max_weight_b_matching` solved by the given solver.
+pub fn compute_max_weight_b_matching_with<W: Weight, P: Preferences<W> + ?Sized, S: MatchingSolver<W>>(solver: &S, left_list: Vec<usize>, right_list: Vec<usize>, units: &[usize], preferences: &P, match_size: usize) -> Result<(W, Assignment<W>), MatchingError> {
+    preferences.validate(&left_list, &right_list)?;
+    let column_units = column_units(&right_list, units)?;
+
+    let columns: HashMap<usize, usize> = right_list.iter().enumerate().map(|(j, &right)| (right, j)).collect();
+    let rows: Vec<Vec<(usize, W)>> = left_list.iter().map(|&left| preferences.edges(left, &columns)).collect();
+
+    let (_, matched_pairs) = solver.solve_b_matching(&rows, &column_units, match_size)?;
+    assignment_from_pairs(&rows, &left_list, &right_list, matched_pairs)
+}
+
+/// Maximum weight matching of size `match_size` in which agent `agent` may use up to
+/// `capacities[agent]` different items and item `item` has `units[item]` units, so a
+/// group may make use of more items than it has members.
+///
+/// The matching is smaller than `match_size` when the agents cannot use that many
+/// items, e.g. because an agent may not take two units of the same item.
+pub fn compute_max_weight_capacitated_matching<W: Weight, P: Preferences<W> + ?Sized>(left_list: Vec<usize>, right_list: Vec<usize>, capacities: &[usize], units: &[usize], preferences: &P, match_size: usize) -> Result<(W, MultiAssignment<W>), MatchingError> {
+    compute_max_weight_capacitated_matching_with(&SuccessiveShortestPathSolver, left_list, right_list, capacities, units, preferences, match_size)
+}
+
+/// `compute_max_weight_capacitated_matching` solved by the given solver.
+pub fn compute_max_weight_capacitated_matching_with<W: Weight, P: Preferences<W> + ?Sized, S: MatchingSolver<W>>(solver: &S, left_list: Vec<usize>, right_list: Vec<usize>, capacities: &[usize], units: &[usize], preferences: &P, match_size: usize) -> Result<(W, MultiAssignment<W>), MatchingError> {
+    preferences.validate(&left_list, &right_list)?;
+    let row_capacities = row_capacities(&left_list, capacities)?;
+    let column_units = column_units(&right_list, units)?;
+
+    let columns: HashMap<usize, usize> = right_list.iter().enumerate().map(|(j, &right)| (right, j)).collect();
+    let rows: Vec<Vec<(usize, W)>> = left_list.iter().map(|&left| preferences.edges(left, &columns)).collect();
+
+    let (_, matched_pairs) = solver.solve_capacitated(&rows, &row_capacities, &column_units, match_size)?;
+    let mut max_weight = W::zero();
+    let mut assignment = MultiAssignment::new();
+    for (i, j) in matched_pairs {
+        let w = edge_weight(&rows[i], j)
+            .ok_or_else(|| MatchingError::BrokenInvariant(format!("the solver matched row {} to column {}, which is not an edge", i, j)))?;
+        max_weight = max_weight + w;
+        assignment.entry(left_list[i]).or_insert_with(Vec::new).push((right_list[j], w));
+    }
+    for items in assignment.values_mut() {
+        items.sort_by_key(|&(item, _)| item);
+    }
+    Ok((max_weight, assignment))
+}
 
-    assert!(left_list.len() <= right_list.len(), "Number of rows must be less than or equal to number of columns.");
+/// Capacities of the agents of `left_list`, looked up by agent id in `capacities`.
+fn row_capacities(left_list: &[usize], capacities: &[usize]) -> Result<Vec<usize>, MatchingError> {
+    left_list
+        .iter()
+        .map(|&agent| capacities.get(agent).copied().ok_or(MatchingError::MissingCapacity { agent, num_agents: capacities.len() }))
+        .collect()
+}
 
-    for (i, &left) in left_list.iter().enumerate() {
-        for (j, &right) in right_list.iter().enumerate() {
-            right_map.insert(j, right_list[j]);
-            weights[i][j] = (preferences[left][right]*(10000000.0)) as i128;
+/// Units of the items of `right_list`, looked up by item id in `units`, which needs
+/// one entry for every item id up to the largest of `right_list`.
+fn column_units(right_list: &[usize], units: &[usize]) -> Result<Vec<usize>, MatchingError> {
+    let num_items = right_list.iter().max().map_or(0, |&item| item + 1);
+    right_list
+        .iter()
+        .map(|&item| units.get(item).copied().ok_or(MatchingError::MissingUnits { item, num_items }))
+        .collect()
+}
+
+/// Weight and assignment of the `(row, column)` pairs a solver matched in `rows`.
+fn assignment_from_pairs<W: Weight>(rows: &[Vec<(usize, W)>], left_list: &[usize], right_list: &[usize], matched_pairs: Vec<(usize, usize)>) -> Result<(W, Assignment<W>), MatchingError> {
+    let mut max_weight = W::zero();
+    let mut assignment: Assignment<W> = HashMap::new();
+    for (i, j) in matched_pairs {
+        let w = edge_weight(&rows[i], j)
+            .ok_or_else(|| MatchingError::BrokenInvariant(format!("the solver matched row {} to column {}, which is not an edge", i, j)))?;
+        max_weight = max_weight + w;
+        assignment.insert(left_list[i], (right_list[j], w));
+    }
+    Ok((max_weight, assignment))
+}
+
+/// Same as `compute_max_weight_assignment`, but among the maximum weight matchings the
+/// one whose items `tie_break` prefers, e.g. the lexicographically smallest set of
+/// item ids for `TieBreak::SmallestItems`.
+pub fn compute_max_weight_assignment_with_tie_break<W: Weight, P: Preferences<W> + ?Sized>(left_list: Vec<usize>, right_list: Vec<usize>, preferences: &P, match_size: usize, tie_break: &TieBreak) -> Result<(W, Assignment<W>), MatchingError> {
+    let largest = left_list.len().min(right_list.len());
+    if match_size > largest {
+        return Err(MatchingError::InfeasibleSize { requested: match_size, largest });
+    }
+    let mut matching = IncrementalMatching::new(left_list, right_list, preferences)?;
+    while matching.size() < match_size && matching.augment().is_some() {}
+    matching.set_tie_break(tie_break.clone());
+    Ok((matching.weight(), matching.assignment()))
+}
+
+/// Same as `compute_max_weight_assignment`, with a `Certificate` of optimality that
+/// `verify_certificate` checks independently of the solver.
+pub fn compute_certified_max_weight_assignment<W: Weight, P: Preferences<W> + ?Sized>(left_list: Vec<usize>, right_list: Vec<usize>, preferences: &P, match_size: usize) -> Result<(W, Assignment<W>, Certificate<W>), MatchingError> {
+    let largest = left_list.len().min(right_list.len());
+    if match_size > largest {
+        return Err(MatchingError::InfeasibleSize { requested: match_size, largest });
+    }
+    let mut matching = IncrementalMatching::new(left_list, right_list, preferences)?;
+    while matching.size() < match_size && matching.augment().is_some() {}
+    Ok((matching.weight(), matching.assignment(), matching.certificate()))
+}
+
+/// Checks that `certificate` proves `assignment` to be a maximum weight matching of
+/// size `match_size` between `left_list` and `right_list`: the assignment is a
+/// matching of that size on edges of `preferences`, the duals are feasible, and
+/// complementary slackness holds.
+///
+/// Nothing of the solver is trusted, so a matching published with its certificate
+/// can be audited with the preferences alone.
+pub fn verify_certificate<W: Weight, P: Preferences<W> + ?Sized>(left_list: &[usize], right_list: &[usize], preferences: &P, match_size: usize, assignment: &Assignment<W>, certificate: &Certificate<W>) -> Result<(), MatchingError> {
+    preferences.validate(left_list, right_list)?;
+    let invalid = |reason: String| Err(MatchingError::InvalidCertificate(reason));
+    let agents: HashSet<usize> = left_list.iter().copied().collect();
+    let items: HashSet<usize> = right_list.iter().copied().collect();
+    let columns: HashMap<usize, usize> = right_list.iter().enumerate().map(|(j, &right)| (right, j)).collect();
+    let eq = |a: W, b: W| !a.definitely_lt(b) && !b.definitely_lt(a);
+
+    // Primal feasibility.
+    if assignment.len() != match_size {
+        return invalid(format!("the matching has size {}, not {}", assignment.len(), match_size));
+    }
+    let mut matched_items = HashSet::new();
+    for (&agent, &(item, value)) in assignment {
+        if !agents.contains(&agent) || !items.contains(&item) {
+            return invalid(format!("agent {} is matched to item {}, which is not a pair of the instance", agent, item));
+        }
+        if !matched_items.insert(item) {
+            return invalid(format!("item {} is matched twice", item));
+        }
+        match preferences.value(agent, item) {
+            Some(w) if eq(w, value) => {}
+            _ => return invalid(format!("agent {} is matched to item {} with value {:?}, which is not its preference", agent, item, value)),
         }
     }
 
-    let (max_weight, matched_list) = fixed_size_max_weight_matching(&weights, match_size);
-    let mut assignments: HashSet<usize> = HashSet::new();
-    for i in matched_list {
-        assignments.insert(right_map[&i]);
+    // Dual feasibility and complementary slackness.
+    let dual = |duals: &HashMap<usize, W>, id: usize| duals.get(&id).copied().unwrap_or_else(W::zero);
+    for (&agent, &u) in &certificate.agent_duals {
+        if u.definitely_lt(W::zero()) {
+            return invalid(format!("agent {} has negative dual {:?}", agent, u));
+        }
+        if W::zero().definitely_lt(u) && !assignment.contains_key(&agent) {
+            return invalid(format!("agent {} is unmatched but has dual {:?}", agent, u));
+        }
     }
-    ((max_weight as f64)/(10000000.0), assignments)
+    for (&item, &v) in &certificate.item_duals {
+        if v.definitely_lt(W::zero()) {
+            return invalid(format!("item {} has negative dual {:?}", item, v));
+        }
+        if W::zero().definitely_lt(v) && !matched_items.contains(&item) {
+            return invalid(format!("item {} is unmatched but has dual {:?}", item, v));
+        }
+    }
+    let is_matched = |agent: usize, item: usize| assignment.get(&agent).is_some_and(|&(matched, _)| matched == item);
+    for (&(agent, item), &z) in &certificate.edge_duals {
+        if z.definitely_lt(W::zero()) {
+            return invalid(format!("edge ({}, {}) has negative dual {:?}", agent, item, z));
+        }
+        if W::zero().definitely_lt(z) && !is_matched(agent, item) {
+            return invalid(format!("edge ({}, {}) is unmatched but has dual {:?}", agent, item, z));
+        }
+    }
+    for &agent in left_list {
+        for (j, w) in preferences.edges(agent, &columns) {
+            let item = right_list[j];
+            let z = certificate.edge_duals.get(&(agent, item)).copied().unwrap_or_else(W::zero);
+            let covered = dual(&certificate.agent_duals, agent) + dual(&certificate.item_duals, item) + certificate.size_dual + z;
+            if covered.definitely_lt(w) {
+                return invalid(format!("the duals cover {:?} of edge ({}, {}) of weight {:?}", covered, agent, item, w));
+            }
+            if is_matched(agent, item) && w.definitely_lt(covered) {
+                return invalid(format!("matched edge ({}, {}) of weight {:?} is not tight, the duals cover {:?}", agent, item, w, covered));
+            }
+        }
+    }
+    Ok(())
 }
 
-fn bellman_ford(graph: &Vec<Vec<(usize, i128)>>, start: usize, end: usize) -> Option<(i128, Vec<usize>)> {
-    let graph_len = graph.len();
-    let mut distance = vec![i128::MAX; graph_len];
-    let mut predecessor = vec![None; graph_len];
-    distance[start] = 0;
+/// Maximum weight matchings of every size from 0 to `max_size` in one solver run.
+///
+/// Entry `s` holds the weight and the items of the best matching of size `s`; the
+/// curve ends early when no larger matching exists.
+pub fn compute_max_weight_curve<W: Weight, P: Preferences<W> + ?Sized>(left_list: Vec<usize>, right_list: Vec<usize>, preferences: &P, max_size: usize) -> Result<Vec<(W, HashSet<usize>)>, MatchingError> {
+    compute_max_weight_curve_with(&SuccessiveShortestPathSolver, left_list, right_list, preferences, max_size)
+}
+
+/// `compute_max_weight_curve` solved by the given solver.
+pub fn compute_max_weight_curve_with<W: Weight, P: Preferences<W> + ?Sized, S: MatchingSolver<W>>(solver: &S, left_list: Vec<usize>, right_list: Vec<usize>, preferences: &P, max_size: usize) -> Result<Vec<(W, HashSet<usize>)>, MatchingError> {
+    preferences.validate(&left_list, &right_list)?;
+    let columns: HashMap<usize, usize> = right_list.iter().enumerate().map(|(j, &right)| (right, j)).collect();
+    let rows: Vec<Vec<(usize, W)>> = left_list.iter().map(|&left| preferences.edges(left, &columns)).collect();
+
+    let curve = solver.solve_curve(&rows, right_list.len(), max_size)?;
+    Ok(curve
+        .into_iter()
+        .map(|(max_weight, matched_pairs)| (max_weight, matched_pairs.into_iter().map(|(_, j)| right_list[j]).collect()))
+        .collect())
+}
 
-    for _ in 0..graph_len - 1 {
-        let mut updated = false;
-        for u in 0..graph_len {
-            if distance[u] == i128::MAX {
-                continue;
+/// Weight of the edge to column `j` in a row sorted by column.
+pub(crate) fn edge_weight<W: Weight>(row: &[(usize, W)], j: usize) -> Option<W> {
+    row.binary_search_by_key(&j, |&(j, _)| j).ok().map(|pos| row[pos].1)
+}
+
+/// Matchings of size `match_size` in non-increasing order of weight, the best first,
+/// so `k_best_assignments(..)?.take(r)` gives the top `r` and how far each is behind
+/// the best.
+///
+/// Each matching is found once by Murty's algorithm: the matchings left after one
+/// has been returned are split into subproblems that force a prefix of its pairs and
+/// forbid the next one, and the best matching of each subproblem, solved by the
+/// solver of size `match_size` minus the forced pairs, waits in a priority queue.
+/// Subproblems are only solved when their parent is returned.
+pub fn k_best_assignments<W: Weight, P: Preferences<W> + ?Sized>(left_list: Vec<usize>, right_list: Vec<usize>, preferences: &P, match_size: usize) -> Result<KBestAssignments<W, SuccessiveShortestPathSolver>, MatchingError> {
+    k_best_assignments_with(&SuccessiveShortestPathSolver, left_list, right_list, preferences, match_size)
+}
+
+/// `k_best_assignments` with the subproblems solved by the given solver.
+pub fn k_best_assignments_with<W: Weight, P: Preferences<W> + ?Sized, S: MatchingSolver<W> + Clone>(solver: &S, left_list: Vec<usize>, right_list: Vec<usize>, preferences: &P, match_size: usize) -> Result<KBestAssignments<W, S>, MatchingError> {
+    preferences.validate(&left_list, &right_list)?;
+    let largest = left_list.len().min(right_list.len());
+    if match_size > largest {
+        return Err(MatchingError::InfeasibleSize { requested: match_size, largest });
+    }
+    let columns: HashMap<usize, usize> = right_list.iter().enumerate().map(|(j, &right)| (right, j)).collect();
+    let rows: Vec<Vec<(usize, W)>> = left_list.iter().map(|&left| preferences.edges(left, &columns)).collect();
+
+    let mut k_best = KBestAssignments { solver: solver.clone(), left_list, right_list, rows, match_size, queue: BinaryHeap::new(), pushed: 0 };
+    k_best.push(Vec::new(), Vec::new())?;
+    Ok(k_best)
+}
+
+/// Iterator of `k_best_assignments`.
+pub struct KBestAssignments<W: Weight, S: MatchingSolver<W>> {
+    solver: S,
+    left_list: Vec<usize>,
+    right_list: Vec<usize>,
+    rows: Vec<Vec<(usize, W)>>,
+    match_size: usize,
+    queue: BinaryHeap<Subproblem<W>>,
+    pushed: usize,
+}
+
+/// Matchings that contain the `forced` pairs and none of the `forbidden` ones, with
+/// the best of them, `pairs`, of weight `weight`.
+struct Subproblem<W> {
+    weight: W,
+    order: usize,
+    forced: Vec<(usize, usize)>,
+    forbidden: Vec<(usize, usize)>,
+    pairs: Vec<(usize, usize)>,
+}
+
+impl<W: Weight> PartialEq for Subproblem<W> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<W: Weight> Eq for Subproblem<W> {}
+
+impl<W: Weight> PartialOrd for Subproblem<W> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Heaviest first, and the earliest found among equal weights
+impl<W: Weight> Ord for Subproblem<W> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.weight.total_cmp(&other.weight).then_with(|| other.order.cmp(&self.order))
+    }
+}
+
+impl<W: Weight, S: MatchingSolver<W>> KBestAssignments<W, S> {
+    /// Solves the subproblem of the given pairs and queues it, unless it has no
+    /// matching of the full size.
+    fn push(&mut self, forced: Vec<(usize, usize)>, forbidden: Vec<(usize, usize)>) -> Result<(), MatchingError> {
+        let forced_rows: HashSet<usize> = forced.iter().map(|&(i, _)| i).collect();
+        let forced_columns: HashSet<usize> = forced.iter().map(|&(_, j)| j).collect();
+        let forbidden_pairs: HashSet<(usize, usize)> = forbidden.iter().copied().collect();
+
+        // The rows and columns left once the forced pairs are taken out.
+        let sub_rows: Vec<usize> = (0..self.rows.len()).filter(|i| !forced_rows.contains(i)).collect();
+        let sub_columns: Vec<usize> = (0..self.right_list.len()).filter(|j| !forced_columns.contains(j)).collect();
+        let position: HashMap<usize, usize> = sub_columns.iter().enumerate().map(|(c, &j)| (j, c)).collect();
+        let rows: Vec<Vec<(usize, W)>> = sub_rows
+            .iter()
+            .map(|&i| {
+                self.rows[i]
+                    .iter()
+                    .filter(|&&(j, _)| !forbidden_pairs.contains(&(i, j)))
+                    .filter_map(|&(j, w)| Some((*position.get(&j)?, w)))
+                    .collect()
+            })
+            .collect();
+
+        let size = self.match_size - forced.len();
+        let matched_pairs = match self.solver.solve_sparse(&rows, sub_columns.len(), size) {
+            Ok((_, matched_pairs)) if matched_pairs.len() == size => matched_pairs,
+            Ok(_) | Err(MatchingError::InfeasibleSize { .. }) => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        let mut pairs: Vec<(usize, usize)> = matched_pairs.into_iter().map(|(r, c)| (sub_rows[r], sub_columns[c])).collect();
+        pairs.extend(forced.iter().copied());
+        pairs.sort();
+        let (weight, _) = assignment_from_pairs(&self.rows, &self.left_list, &self.right_list, pairs.clone())?;
+
+        self.queue.push(Subproblem { weight, order: self.pushed, forced, forbidden, pairs });
+        self.pushed += 1;
+        Ok(())
+    }
+}
+
+impl<W: Weight, S: MatchingSolver<W>> Iterator for KBestAssignments<W, S> {
+    type Item = Result<(W, Assignment<W>), MatchingError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Subproblem { forced, forbidden, pairs, .. } = self.queue.pop()?;
+        // The i-th child keeps the first i free pairs of the matching and forbids the
+        // next one, so the children split the other matchings of the subproblem.
+        let free: Vec<(usize, usize)> = pairs.iter().copied().filter(|pair| !forced.contains(pair)).collect();
+        for (t, &pair) in free.iter().enumerate() {
+            let mut child_forced = forced.clone();
+            child_forced.extend_from_slice(&free[..t]);
+            let mut child_forbidden = forbidden.clone();
+            child_forbidden.push(pair);
+            if let Err(err) = self.push(child_forced, child_forbidden) {
+                return Some(Err(err));
             }
-            for &(v, weight) in &graph[u] {
-                if distance[u] + weight < distance[v] {
-                    distance[v] = distance[u] + weight;
-                    predecessor[v] = Some(u);
-                    updated = true;
-                }
+        }
+        Some(assignment_from_pairs(&self.rows, &self.left_list, &self.right_list, pairs))
+    }
+}
+
+/// Maximum weight matching of one group that is kept between calls.
+///
+/// The matching is grown one augmenting path at a time and items can be added or
+/// withdrawn in between, so the round robin never has to solve a group's matching
+/// from scratch when its bundle grows by one item.
+pub struct IncrementalMatching<W: Weight> {
+    left_list: Vec<usize>,
+    right_list: Vec<usize>,
+    column: HashMap<usize, usize>,
+    ssp: SuccessiveShortestPaths<W>,
+    tie_break: TieBreak,
+}
+
+impl<W: Weight> IncrementalMatching<W> {
+    pub fn new<P: Preferences<W> + ?Sized>(left_list: Vec<usize>, right_list: Vec<usize>, preferences: &P) -> Result<Self, MatchingError> {
+        let row_capacities = vec![1; left_list.len()];
+        let column_units = vec![1; right_list.len()];
+        Self::build(left_list, right_list, row_capacities, column_units, preferences)
+    }
+
+    /// Same as `new`, but item `item` has `units[item]` identical units, each of which
+    /// may go to a different agent.
+    pub fn with_units<P: Preferences<W> + ?Sized>(left_list: Vec<usize>, right_list: Vec<usize>, units: &[usize], preferences: &P) -> Result<Self, MatchingError> {
+        let row_capacities = vec![1; left_list.len()];
+        let column_units = column_units(&right_list, units)?;
+        Self::build(left_list, right_list, row_capacities, column_units, preferences)
+    }
+
+    /// Same as `with_units`, but agent `agent` may use up to `capacities[agent]`
+    /// different items.
+    pub fn with_capacities<P: Preferences<W> + ?Sized>(left_list: Vec<usize>, right_list: Vec<usize>, capacities: &[usize], units: &[usize], preferences: &P) -> Result<Self, MatchingError> {
+        let row_capacities = row_capacities(&left_list, capacities)?;
+        let column_units = column_units(&right_list, units)?;
+        Self::build(left_list, right_list, row_capacities, column_units, preferences)
+    }
+
+    fn build<P: Preferences<W> + ?Sized>(left_list: Vec<usize>, right_list: Vec<usize>, row_capacities: Vec<usize>, column_units: Vec<usize>, preferences: &P) -> Result<Self, MatchingError> {
+        preferences.validate(&left_list, &right_list)?;
+        let column: HashMap<usize, usize> = right_list.iter().enumerate().map(|(j, &right)| (right, j)).collect();
+        let rows: Vec<Vec<(usize, W)>> = left_list.iter().map(|&left| preferences.edges(left, &column)).collect();
+        let ssp = SuccessiveShortestPaths::new(&rows, &row_capacities, &column_units);
+        Ok(IncrementalMatching {
+            left_list,
+            right_list,
+            column,
+            ssp,
+            tie_break: TieBreak::Solver,
+        })
+    }
+
+    /// Weight of the current matching.
+    pub fn weight(&self) -> W {
+        -self.ssp.cost()
+    }
+
+    /// Number of matched items.
+    pub fn size(&self) -> usize {
+        self.ssp.flow
+    }
+
+    /// Items in the current matching.
+    pub fn assignments(&self) -> HashSet<usize> {
+        self.ssp.matched_columns().into_iter().map(|j| self.right_list[j]).collect()
+    }
+
+    /// Item and value each matched agent receives, for agents that use one item; see
+    /// `multi_assignment` otherwise.
+    pub fn assignment(&self) -> Assignment<W> {
+        self.ssp
+            .matched_pairs()
+            .into_iter()
+            .map(|(i, j)| (self.left_list[i], (self.right_list[j], -self.ssp.arc_cost(i, j))))
+            .collect()
+    }
+
+    /// Duals proving the current matching optimal among the matchings of its size,
+    /// with the capacities and units the matching was built with.
+    ///
+    /// They are read off the Johnson potentials `p` of the solver, which leave every
+    /// residual arc with a non-negative reduced cost: `lambda = p(source) - p(sink)`,
+    /// `u_i = max(0, p(i) - p(source))` and `v_j = max(0, p(sink) - p(j))`, so that
+    /// `u_i + v_j + lambda >= p(i) - p(j) >= w_ij` on the edges without flow.
+    pub fn certificate(&self) -> Certificate<W> {
+        let ssp = &self.ssp;
+        let (source, sink) = (ssp.source(), ssp.sink());
+        let potential = &ssp.network.potential;
+        let positive = |x: W| if W::zero() < x { x } else { W::zero() };
+        let size_dual = potential[source] - potential[sink];
+        let agent_duals: HashMap<usize, W> =
+            self.left_list.iter().enumerate().map(|(i, &agent)| (agent, positive(potential[i] - potential[source]))).collect();
+        // Withdrawn items are no longer part of the LP.
+        let item_duals: HashMap<usize, W> = self
+            .right_list
+            .iter()
+            .enumerate()
+            .filter(|&(j, _)| ssp.units(j).0 > 0)
+            .map(|(j, &item)| (item, positive(potential[sink] - potential[ssp.node(j)])))
+            .collect();
+        // An edge with flow may have a negative reduced cost once its agent or item has
+        // other residual arcs; its capacity of one pays for the difference.
+        let edge_duals = ssp
+            .matched_pairs()
+            .into_iter()
+            .filter_map(|(i, j)| {
+                let (agent, item) = (self.left_list[i], self.right_list[j]);
+                let z = -ssp.arc_cost(i, j) - agent_duals[&agent] - item_duals.get(&item).copied().unwrap_or_else(W::zero) - size_dual;
+                W::zero().definitely_lt(z).then_some(((agent, item), z))
+            })
+            .collect();
+        Certificate { agent_duals, item_duals, size_dual, edge_duals }
+    }
+
+    /// Items and values each matched agent receives.
+    pub fn multi_assignment(&self) -> MultiAssignment<W> {
+        let mut assignment = MultiAssignment::new();
+        for (i, j) in self.ssp.matched_pairs() {
+            assignment.entry(self.left_list[i]).or_insert_with(Vec::new).push((self.right_list[j], -self.ssp.arc_cost(i, j)));
+        }
+        for items in assignment.values_mut() {
+            items.sort_by_key(|&(item, _)| item);
+        }
+        assignment
+    }
+
+    /// Grows the matching by one along a shortest augmenting path.
+    ///
+    /// The units matched before stay matched, so the returned item is the only one
+    /// of which one more unit enters the matching. Returns `None` when no larger
+    /// matching exists.
+    ///
+    /// Under a tie-breaking policy the item is the one the policy prefers among all
+    /// the items that give a matching of the same weight.
+    pub fn augment(&mut self) -> Option<usize> {
+        let j = self.ssp.augment()?;
+        let Some(keys) = self.keys() else {
+            return Some(self.right_list[j]);
+        };
+        let (replacements, previous) = self.ssp.replacements(j);
+        match replacements.into_iter().filter(|&x| keys[x] < keys[j]).min_by_key(|&x| keys[x]) {
+            Some(x) => {
+                self.ssp.exchange(j, x, &previous);
+                Some(self.right_list[x])
             }
+            None => Some(self.right_list[j]),
         }
-        if !updated {
-            break;
+    }
+
+    /// Breaks ties between optimal matchings by `tie_break` from now on, starting with
+    /// the current matching, which becomes the one of the same weight and size whose
+    /// items the policy prefers.
+    pub fn set_tie_break(&mut self, tie_break: TieBreak) {
+        self.tie_break = tie_break;
+        self.break_ties();
+    }
+
+    /// Keys of the columns under the tie-breaking policy, if there is one.
+    fn keys(&self) -> Option<Vec<(u64, usize)>> {
+        self.right_list.iter().map(|&item| self.tie_break.key(item)).collect()
+    }
+
+    fn break_ties(&mut self) {
+        if let Some(keys) = self.keys() {
+            self.ssp.prefer(&keys);
         }
     }
 
-    if distance[end] == i128::MAX {
-        return None;
+    /// Makes one more unit of an item available to the group.
+    ///
+    /// The matching is kept as long as the new unit does not improve it; otherwise
+    /// the matching of the current size is solved again.
+    pub fn add_item<P: Preferences<W> + ?Sized>(&mut self, item: usize, preferences: &P) -> Result<(), MatchingError> {
+        if let Some(&j) = self.column.get(&item) {
+            if self.ssp.add_unit(j) {
+                self.break_ties();
+            }
+            return Ok(());
+        }
+        preferences.validate(&self.left_list, &[item])?;
+        let costs: Vec<(usize, W)> = self
+            .left_list
+            .iter()
+            .enumerate()
+            .filter_map(|(i, &left)| Some((i, -preferences.value(left, item)?)))
+            .collect();
+        self.column.insert(item, self.right_list.len());
+        self.right_list.push(item);
+        if self.ssp.add_column(&costs) {
+            self.break_ties();
+        }
+        Ok(())
     }
 
-    let mut path = vec![];
-    let mut current = end;
-    while let Some(prev) = predecessor[current] {
-        path.push(current);
-        current = prev;
-        if current == start {
-            break;
+    /// Withdraws one unit of an item, e.g. because another group has taken it.
+    ///
+    /// Withdrawing an unmatched unit keeps the matching optimal; withdrawing a
+    /// matched one solves the matching one size smaller again.
+    pub fn remove_item(&mut self, item: usize) {
+        if let Some(&j) = self.column.get(&item) {
+            if self.ssp.remove_unit(j) {
+                self.break_ties();
+            }
         }
     }
-    path.push(start);
-    path.reverse();
+}
 
-    Some((distance[end], path))
+/// Maximum weight matching of `match_size` items, ready to answer how its weight
+/// changes when one preference does, see `Sensitivity`.
+///
+/// Sparse preferences may leave the matching smaller, in which case the answers are
+/// about matchings of that size.
+pub fn compute_max_weight_sensitivity<W: Weight, P: Preferences<W> + ?Sized>(left_list: Vec<usize>, right_list: Vec<usize>, preferences: &P, match_size: usize) -> Result<Sensitivity<W>, MatchingError> {
+    let largest = left_list.len().min(right_list.len());
+    if match_size > largest {
+        return Err(MatchingError::InfeasibleSize { requested: match_size, largest });
+    }
+    let mut matching = IncrementalMatching::new(left_list, right_list, preferences)?;
+    while matching.size() < match_size && matching.augment().is_some() {}
+    Ok(Sensitivity::new(matching))
 }
 
-// Function to find the maximum weight matching of a fixed size
-fn fixed_size_max_weight_matching(weights: &[Vec<i128>], k: usize) -> (i128, Vec<usize>) {
-    let n = weights.len();
-    let m = weights[0].len();
+/// Single-edge sensitivity of an optimal matching.
+///
+/// Compared with the optimal flow, the best matching of the same size with edge
+/// `(i, j)` of a new weight `w` either
+///
+/// - keeps every pair if `(i, j)` is matched, gaining `w` minus its old weight, or
+///   drops `(i, j)` along the cheapest residual cycle through its reverse arc;
+/// - keeps every pair if `(i, j)` is not matched, or adds `(i, j)` along the
+///   cheapest residual cycle through it, gaining `w`.
+///
+/// Both cycles close with a shortest residual path between `i` and `j`. Dijkstra on
+/// the solver's reduced costs gives all of them from one endpoint at once, so after
+/// one run per agent or item, which is cached, every query takes constant time
+/// instead of a new matching.
+pub struct Sensitivity<W: Weight> {
+    matching: IncrementalMatching<W>,
+    row: HashMap<usize, usize>,
+    distances: HashMap<usize, Vec<Option<W>>>,
+}
 
-    assert!(n <= m, "Number of rows must be less than or equal to number of columns.");
-    assert!(k <= n, "k must be less than or equal to number of rows.");
+impl<W: Weight> Sensitivity<W> {
+    pub fn new(matching: IncrementalMatching<W>) -> Self {
+        let row = matching.left_list.iter().enumerate().map(|(i, &left)| (left, i)).collect();
+        Sensitivity { matching, row, distances: HashMap::new() }
+    }
 
-    let mut matched_left = vec![usize::MAX; n];
-    let mut matched_right = vec![usize::MAX; m];
-    let mut max_weight = 0;
+    /// Weight of the optimal matching.
+    pub fn weight(&self) -> W {
+        self.matching.weight()
+    }
 
-    // Updating the matching increasing the size from 1 to k.
-    for size in 1..=k {
-
-        let mut g = vec![vec![]; n + m + 2];
-        for i in 0..n {
-            for j in 0..m {
-                if matched_left[i] == usize::MAX && matched_right[j] == usize::MAX {
-                    g[n + m].push((i, 0));
-                    g[i].push((j + n, -1 * weights[i][j]));
-                    g[j + n].push((n + m + 1, 0));
-                } else if matched_left[i] == usize::MAX && matched_right[j] != usize::MAX {
-                    g[n + m].push((i, 0));
-                    g[i].push((j + n, -1 * weights[i][j]));
-                } else if matched_left[i] != usize::MAX && matched_right[j] == usize::MAX {
-                    g[i].push((j + n, -1 * weights[i][j]));
-                    g[j + n].push((n + m + 1, 0));
-                } else {
-                    if matched_left[i] == j && matched_right[j] == i {
-                        g[j + n].push((i, weights[i][j])); // Edges included in the matching are reversed and their weights are set to positive.
-                    }
-                }
+    /// Weight of the best matching of the same size once `agent` values `item` at
+    /// `value`, or `None` when the agent or the item is not part of the matching
+    /// problem. Agents may get a value for items they had no edge to.
+    pub fn weight_if(&mut self, agent: usize, item: usize, value: W) -> Option<W> {
+        let i = *self.row.get(&agent)?;
+        let j = *self.matching.column.get(&item)?;
+        let ssp = &self.matching.ssp;
+        let v = ssp.node(j);
+        let weight = self.matching.weight();
+        let max = |a: W, b: Option<W>| match b {
+            Some(b) if a < b => b,
+            _ => a,
+        };
+        match ssp.network.graph[i].iter().find(|arc| arc.to == v && arc.cap < arc.capacity) {
+            Some(arc) => {
+                let old = -arc.cost;
+                let without = self.distance(i, v).map(|d| weight - old - d);
+                Some(max(weight - old + value, without))
+            }
+            None => {
+                let with = self.distance(v, i).map(|d| weight + value - d);
+                Some(max(weight, with))
             }
         }
-        // println!("matched_left {:?}", matched_left);
-        // println!("matched_right {:?}", matched_right);
-        // println!("g {:?}", g);
-        //let (max_end, max_path_weight) = max_alternating_path(&g, &matched_left, &matched_right);
-        
+    }
 
-        let start = n + m;
-        let end = n + m + 1;
-        if let Some((cost, path)) = bellman_ford(&g, start, end) {
-            // println!("The cost of the shortest path is: {}", -cost);
-            // println!("Path: {:?}", path);
-
-            for i in 1..(path.len() - 1) {
-                let x = path[i];
-                let y = path[i + 1];
-                if x != n + m && y != m + n + 1 {
-                    if x < n && n - 1 < y {
-                        matched_left[x] = y-n;
-                        matched_right[y-n] = x;
-                        max_weight += weights[x][y-n];
-                    } else if y < n && n - 1 < x {
-                        max_weight -= weights[y][x-n];
-                    }
-                }
+    /// Cost of the shortest residual path from node `from` to node `to`.
+    fn distance(&mut self, from: usize, to: usize) -> Option<W> {
+        let ssp = &self.matching.ssp;
+        self.distances.entry(from).or_insert_with(|| ssp.distances(from))[to]
+    }
+}
+
+/// Flow network `source -> left -> right -> sink` for the bipartite matching problem.
+///
+/// Left vertex `i` is node `i`, the source is node `n`, the sink is node `n + 1` and
+/// right vertex `j` is node `n + 2 + j`. The arc from the source to left vertex `i` has
+/// the capacity of `i`, the arc from right vertex `j` to the sink the capacity of its
+/// number of units, every other arc has capacity one, and the arc
+/// of an edge from `i` to `j` of weight `w` costs `-w`, so a minimum cost flow of value
+/// `k` is a maximum weight matching of size `k`. Only the given edges become arcs, so
+/// sparse graphs stay small.
+///
+/// The flow itself is a `FlowNetwork`; this layer knows the layout, so it sets the
+/// potentials of the empty flow layer by layer instead of by Bellman-Ford and keeps
+/// them valid as right vertices and units come and go.
+struct SuccessiveShortestPaths<W> {
+    n: usize,
+    m: usize,
+    network: FlowNetwork<W>,
+    flow: usize,
+}
+
+impl<W: Weight> SuccessiveShortestPaths<W> {
+    /// Network of left vertices of capacities `capacities`, right vertices with
+    /// `units[j]` units of right vertex `j`, and the edges `rows[i]`, given as
+    /// `(column, weight)` pairs, of every left vertex `i`.
+    fn new(rows: &[Vec<(usize, W)>], capacities: &[usize], units: &[usize]) -> Self {
+        let n = rows.len();
+        let m = units.len();
+        let mut ssp = SuccessiveShortestPaths { n, m, network: FlowNetwork::new(n + 2 + m), flow: 0 };
+        let (source, sink) = (ssp.source(), ssp.sink());
+        for (i, &capacity) in capacities.iter().enumerate() {
+            ssp.network.add_arc(source, i, capacity, W::zero());
+        }
+        for (i, row) in rows.iter().enumerate() {
+            for &(j, w) in row {
+                ssp.network.add_arc(i, n + 2 + j, 1, -w);
             }
-            
+        }
+        for (j, &units) in units.iter().enumerate() {
+            ssp.network.add_arc(n + 2 + j, sink, units, W::zero());
+        }
+        ssp.reset_potentials();
+        ssp
+    }
+
+    fn source(&self) -> usize {
+        self.n
+    }
+
+    fn sink(&self) -> usize {
+        self.n + 1
+    }
+
+    fn node(&self, j: usize) -> usize {
+        self.n + 2 + j
+    }
+
+    /// Cost of the flow, minus the weight of the matching.
+    fn cost(&self) -> W {
+        self.network.cost()
+    }
+
+    /// Adds a right vertex of one unit with arcs from the left vertices of `costs`,
+    /// given as `(left vertex, cost)` pairs. Returns whether the matching was solved
+    /// again.
+    fn add_column(&mut self, costs: &[(usize, W)]) -> bool {
+        let v = self.network.add_node();
+        self.m += 1;
+        for &(i, cost) in costs {
+            self.network.add_arc(i, v, 1, cost);
+        }
+        let sink = self.sink();
+        self.network.add_arc(v, sink, 1, W::zero());
+
+        // An unmatched vertex only has arcs from the left and to the sink, so the
+        // potentials stay valid if one fits between the two; otherwise the new
+        // vertex may improve the matching and it is solved again.
+        let potential = self.entry_potential(v, true);
+        self.network.potential[v] = potential;
+        let stale = potential.definitely_lt(self.network.potential[sink]);
+        if stale {
+            self.resolve(self.flow);
+        }
+        stale
+    }
+
+    /// Smallest potential of right vertex `v` allowed by its arcs from the left, or
+    /// the shortest distance to it from the source when `relative` is false.
+    fn entry_potential(&self, v: usize, relative: bool) -> W {
+        self.network.graph[v]
+            .iter()
+            .filter(|arc| arc.to < self.n)
+            .map(|arc| if relative { self.network.potential[arc.to] - arc.cost } else { -arc.cost })
+            .min_by(Weight::total_cmp)
+            .unwrap_or_else(W::zero)
+    }
+
+    /// Potentials of the empty flow, which leaves an acyclic network, so they are the
+    /// shortest distances from the source taken layer by layer.
+    fn reset_potentials(&mut self) {
+        let sink = self.sink();
+        self.network.potential.iter_mut().for_each(|p| *p = W::zero());
+        for j in 0..self.m {
+            let v = self.node(j);
+            self.network.potential[v] = self.entry_potential(v, false);
+        }
+        self.network.potential[sink] = (0..self.m)
+            .filter(|&j| self.units(j).0 > 0)
+            .map(|j| self.network.potential[self.node(j)])
+            .min_by(Weight::total_cmp)
+            .unwrap_or_else(W::zero);
+    }
+
+    /// Position of the arc from right vertex `j` to the sink in its adjacency list.
+    fn sink_arc(&self, j: usize) -> usize {
+        let sink = self.sink();
+        self.network.graph[self.node(j)].iter().position(|arc| arc.to == sink).expect("every right vertex has an arc to the sink")
+    }
+
+    /// Number of units of right vertex `j`, and how many of them carry flow.
+    fn units(&self, j: usize) -> (usize, usize) {
+        let arc = &self.network.graph[self.node(j)][self.sink_arc(j)];
+        (arc.capacity, arc.capacity - arc.cap)
+    }
+
+    /// Adds one unit of right vertex `j`. Returns whether the matching was solved
+    /// again.
+    fn add_unit(&mut self, j: usize) -> bool {
+        let (v, idx) = (self.node(j), self.sink_arc(j));
+        let (units, used) = self.units(j);
+        self.network.graph[v][idx].capacity += 1;
+        self.network.graph[v][idx].cap += 1;
+        if used < units {
+            return false;
+        }
+        // The arc to the sink comes back to the residual network. A vertex without
+        // flow only has arcs from the left and to the sink, like a new one, so its
+        // potential may rise to fit both; otherwise the matching is solved again when
+        // the arc breaks the potentials.
+        if used == 0 {
+            self.network.potential[v] = self.entry_potential(v, true);
+        }
+        let stale = self.network.potential[v].definitely_lt(self.network.potential[self.sink()]);
+        if stale {
+            self.resolve(self.flow);
+        }
+        stale
+    }
+
+    /// Withdraws one unit of right vertex `j`, if it has any left. Returns whether the
+    /// matching was solved again.
+    fn remove_unit(&mut self, j: usize) -> bool {
+        let (v, idx) = (self.node(j), self.sink_arc(j));
+        let (units, used) = self.units(j);
+        if units == 0 {
+            return false;
+        }
+        self.network.graph[v][idx].capacity -= 1;
+        // Withdrawing an unused unit only removes residual capacity, so the
+        // potentials stay valid; withdrawing a used one leaves one unit too many.
+        if used < units {
+            self.network.graph[v][idx].cap -= 1;
+            false
         } else {
-            println!("No augmenting path found or negative cycle detected");
-            break;
+            self.resolve(self.flow - 1);
+            true
         }
-        // println!("matched_left {:?}", matched_left);
-        // println!("matched_right {:?}", matched_right);
+    }
+
+    /// Solves the matching of the given size again from the empty flow.
+    fn resolve(&mut self, size: usize) {
+        self.network.clear_flow();
+        self.flow = 0;
+        self.reset_potentials();
+
+        for _ in 0..size {
+            self.augment();
+        }
+    }
+
+    /// Costs of the shortest residual paths from node `from` to every node, `None`
+    /// for the unreachable ones.
+    fn distances(&self, from: usize) -> Vec<Option<W>> {
+        self.network.distances(from)
+    }
+
+    /// Sends one unit of flow along a shortest augmenting path.
+    ///
+    /// Returns the right vertex that became matched, or `None` when the sink is no
+    /// longer reachable from the source.
+    fn augment(&mut self) -> Option<usize> {
+        let (_, last) = self.network.augment(self.source(), self.sink(), 1)?;
+        self.flow += 1;
+        Some(last - self.n - 2)
+    }
 
-        // println!("maximum weight of matching with {} edges {}", size, max_weight);
-        let mut l_num = 0;
-        for i in 0..matched_left.len() {
-            if matched_left[i] != usize::MAX {
-                l_num += 1;
+    /// Right vertices that can take over one unit of flow from right vertex `j` at
+    /// the same cost, and the arc each node on the way was entered by.
+    ///
+    /// A unit moves from `j` to `x` along a cycle `sink -> j -> ... -> x -> sink` of
+    /// tight arcs. Any optimal flow with one unit moved from `j` to `x` differs from
+    /// the current one by such a cycle, so these are all the right vertices that can
+    /// replace `j` without changing the weight.
+    fn replacements(&self, j: usize) -> (Vec<usize>, Previous) {
+        let (start, sink) = (self.node(j), self.sink());
+        let graph = &self.network.graph;
+        let mut previous: Previous = vec![None; graph.len()];
+        let from_sink = graph[sink].iter().position(|arc| arc.to == start);
+        if !from_sink.is_some_and(|idx| self.network.is_tight(sink, idx)) {
+            return (Vec::new(), previous);
+        }
+        let mut visited = vec![false; graph.len()];
+        visited[start] = true;
+        visited[sink] = true;
+        let mut queue = std::collections::VecDeque::from([start]);
+        while let Some(u) = queue.pop_front() {
+            for (idx, arc) in graph[u].iter().enumerate() {
+                if !visited[arc.to] && self.network.is_tight(u, idx) {
+                    visited[arc.to] = true;
+                    previous[arc.to] = Some((u, idx));
+                    queue.push_back(arc.to);
+                }
             }
         }
-        let mut r_num = 0;
-        for j in 0..matched_right.len() {
-            if matched_right[j] != usize::MAX {
-                r_num += 1;
+        let replacements = (0..self.m)
+            .filter(|&x| x != j && visited[self.node(x)])
+            .filter(|&x| self.network.is_tight(self.node(x), self.sink_arc(x)))
+            .collect();
+        (replacements, previous)
+    }
+
+    /// Moves one unit of flow from right vertex `j` to right vertex `x`, one of the
+    /// `replacements` of `j` found with `previous`.
+    fn exchange(&mut self, j: usize, x: usize, previous: &[Option<(usize, usize)>]) {
+        let sink = self.sink();
+        let mut path = vec![(self.node(x), self.sink_arc(x))];
+        let mut v = self.node(x);
+        while let Some((u, idx)) = previous[v] {
+            path.push((u, idx));
+            v = u;
+        }
+        let start = self.node(j);
+        let from_sink =
+            self.network.graph[sink].iter().position(|arc| arc.to == start).expect("a matched right vertex has flow to the sink");
+        path.push((sink, from_sink));
+        for (u, idx) in path {
+            self.network.push(u, idx, 1);
+        }
+    }
+
+    /// Moves flow to right vertices of smaller key at the same cost for as long as
+    /// possible.
+    ///
+    /// The matched right vertices of the optimal flows of one value are the bases of
+    /// a matroid, so when no single move improves them they have the smallest keys
+    /// of all optimal flows: the sorted keys are lexicographically smallest.
+    fn prefer(&mut self, keys: &[(u64, usize)]) {
+        loop {
+            let mut matched = self.matched_columns();
+            matched.sort_by_key(|&j| std::cmp::Reverse(keys[j]));
+            let exchange = matched.into_iter().find_map(|j| {
+                let (replacements, previous) = self.replacements(j);
+                let x = replacements.into_iter().filter(|&x| keys[x] < keys[j]).min_by_key(|&x| keys[x])?;
+                Some((j, x, previous))
+            });
+            match exchange {
+                Some((j, x, previous)) => self.exchange(j, x, &previous),
+                None => break,
             }
         }
-        assert!(size == l_num && size == r_num, "l_num and r_num must be the same as size k.")
     }
-    
-    let right_matched: Vec<usize> = matched_right.iter().enumerate().filter_map(|(i, &v)| if v != usize::MAX { Some(i) } else { None }).collect();
-    // println!("max_weight {:?}", max_weight);
-    // println!("right_matched {:?}", right_matched);
-    (max_weight, right_matched)
+
+    /// Whether right vertex `j` carries flow.
+    fn matched(&self, j: usize) -> bool {
+        self.units(j).1 > 0
+    }
+
+    /// Right vertices currently matched, in increasing order.
+    fn matched_columns(&self) -> Vec<usize> {
+        (0..self.m).filter(|&j| self.matched(j)).collect()
+    }
+
+    /// Matched `(left, right)` pairs, in increasing order of the left vertex.
+    fn matched_pairs(&self) -> Vec<(usize, usize)> {
+        (0..self.n)
+            .flat_map(|i| {
+                self.network.graph[i]
+                    .iter()
+                    .filter(|arc| arc.to >= self.n + 2 && arc.cap < arc.capacity)
+                    .map(move |arc| (i, arc.to - self.n - 2))
+            })
+            .collect()
+    }
+
+    /// Cost of the arc from left vertex `i` to right vertex `j`.
+    fn arc_cost(&self, i: usize, j: usize) -> W {
+        let v = self.node(j);
+        self.network.graph[i].iter().find(|arc| arc.to == v).map_or_else(W::zero, |arc| arc.cost)
+    }
+}
+
+// Checks that the weights matrix is rectangular, has no NaN, no more rows than columns and at least k rows, and returns its dimensions
+pub(crate) fn check_dense<W: Weight>(weights: &[Vec<W>], k: usize) -> Result<(usize, usize), MatchingError> {
+    let n = weights.len();
+    let m = weights.first().map_or(0, Vec::len);
+    weights.validate(&(0..n).collect::<Vec<usize>>(), &(0..m).collect::<Vec<usize>>())?;
+    if n > m {
+        return Err(MatchingError::TooManyRows { rows: n, columns: m });
+    }
+    if k > n {
+        return Err(MatchingError::InfeasibleSize { requested: k, largest: n });
+    }
+    Ok((n, m))
+}
+
+// Checks that the edges of the graph end at one of its m columns, have no NaN, and that k fits both sides
+pub(crate) fn check_sparse<W: Weight>(rows: &[Vec<(usize, W)>], m: usize, k: usize) -> Result<(), MatchingError> {
+    check_edges(rows, m)?;
+    let largest = rows.len().min(m);
+    if k > largest {
+        return Err(MatchingError::InfeasibleSize { requested: k, largest });
+    }
+    Ok(())
+}
+
+// Same as check_sparse for the graph whose column j has units[j] units
+pub(crate) fn check_b_matching<W: Weight>(rows: &[Vec<(usize, W)>], units: &[usize], k: usize) -> Result<(), MatchingError> {
+    check_capacitated(rows, &vec![1; rows.len()], units, k)
+}
+
+// Same as check_b_matching when row i has capacity capacities[i]
+pub(crate) fn check_capacitated<W: Weight>(rows: &[Vec<(usize, W)>], capacities: &[usize], units: &[usize], k: usize) -> Result<(), MatchingError> {
+    check_edges(rows, units.len())?;
+    if capacities.len() < rows.len() {
+        return Err(MatchingError::MissingCapacity { agent: capacities.len(), num_agents: capacities.len() });
+    }
+    let largest = capacities.iter().sum::<usize>().min(units.iter().sum());
+    if k > largest {
+        return Err(MatchingError::InfeasibleSize { requested: k, largest });
+    }
+    Ok(())
+}
+
+fn check_edges<W: Weight>(rows: &[Vec<(usize, W)>], m: usize) -> Result<(), MatchingError> {
+    for (i, row) in rows.iter().enumerate() {
+        for &(j, w) in row {
+            if j >= m {
+                return Err(MatchingError::ItemOutOfRange { agent: i, item: j, num_items: m });
+            }
+            if w.is_nan() {
+                return Err(MatchingError::NotANumber { agent: i, item: j });
+            }
+        }
+    }
+    Ok(())
+}
+
+// Function to find the maximum weight matching of a fixed size, returned as (row, column) pairs
+pub(crate) fn fixed_size_max_weight_matching<W: Weight>(weights: &[Vec<W>], k: usize) -> Result<(W, Vec<(usize, usize)>), MatchingError> {
+    let (_, m) = check_dense(weights, k)?;
+
+    let rows: Vec<Vec<(usize, W)>> = weights.iter().map(|row| row.iter().copied().enumerate().collect()).collect();
+    Ok(sparse_fixed_size_max_weight_matching(&rows, m, k))
+}
+
+// Same as fixed_size_max_weight_matching on the graph whose row i has the (column, weight) edges rows[i];
+// the matching is smaller than k when the graph has no matching of size k
+pub(crate) fn sparse_fixed_size_max_weight_matching<W: Weight>(rows: &[Vec<(usize, W)>], m: usize, k: usize) -> (W, Vec<(usize, usize)>) {
+    sparse_fixed_size_max_weight_b_matching(rows, &vec![1; m], k)
+}
+
+// Same as sparse_fixed_size_max_weight_matching when column j may be matched to units[j] rows
+pub(crate) fn sparse_fixed_size_max_weight_b_matching<W: Weight>(rows: &[Vec<(usize, W)>], units: &[usize], k: usize) -> (W, Vec<(usize, usize)>) {
+    sparse_fixed_size_max_weight_capacitated_matching(rows, &vec![1; rows.len()], units, k)
+}
+
+// Same as sparse_fixed_size_max_weight_b_matching when row i may be matched to capacities[i] columns
+pub(crate) fn sparse_fixed_size_max_weight_capacitated_matching<W: Weight>(rows: &[Vec<(usize, W)>], capacities: &[usize], units: &[usize], k: usize) -> (W, Vec<(usize, usize)>) {
+    let mut ssp = SuccessiveShortestPaths::new(rows, capacities, units);
+
+    // Updating the matching increasing the size from 1 to k.
+    for _ in 1..=k {
+        if ssp.augment().is_none() {
+            break;
+        }
+    }
+
+    (-ssp.cost(), ssp.matched_pairs())
+}
+
+// The maximum weight matchings of every size from 0 to k that the successive shortest paths pass through
+pub(crate) fn sparse_max_weight_matching_curve<W: Weight>(rows: &[Vec<(usize, W)>], m: usize, k: usize) -> Vec<(W, Vec<(usize, usize)>)> {
+    let mut ssp = SuccessiveShortestPaths::new(rows, &vec![1; rows.len()], &vec![1; m]);
+    let mut curve = vec![(W::zero(), Vec::new())];
+    while curve.len() <= k && ssp.augment().is_some() {
+        curve.push((-ssp.cost(), ssp.matched_pairs()));
+    }
+    curve
 }
 
 
@@ -169,7 +1143,7 @@ mod tests {
     fn test_kuhn_munkres() {
 
         use pathfinding::kuhn_munkres::*;
-        use pathfinding::{matrix,matrix::{Matrix}};
+        use pathfinding::matrix::Matrix;
 
         let mut m = Matrix::new(2, 2, 0);
         println!("{:?}",m);
@@ -184,7 +1158,7 @@ mod tests {
 
         let max_matching = kuhn_munkres(&m);
         let mut max_val = 0;
-        for i in 0..2 as usize{
+        for i in 0..2 {
             println!("{:?}",max_matching.1[i]);
             max_val += m[(i, max_matching.1[i])];
         }
@@ -198,7 +1172,7 @@ mod tests {
         vec![4, 15, 6]
         ];
         println!("{:?}",weights);
-        let (max_weight, matched) = fixed_size_max_weight_matching(&weights, 2);
+        let (max_weight, matched) = fixed_size_max_weight_matching(&weights, 2).unwrap();
         println!("Max weight: {}", max_weight);
         println!("Matched indices: {:?}", matched);
         assert_eq!(max_weight, 25);
@@ -209,13 +1183,670 @@ mod tests {
             vec![4, 10, 21],
             vec![7, 21, 30]
         ];
-        let (max_weight, matched) = fixed_size_max_weight_matching(&weights, 3);
+        let (max_weight, matched) = fixed_size_max_weight_matching(&weights, 3).unwrap();
         println!("Max weight: {}", max_weight);
         println!("Matched indices: {:?}", matched);
         assert_eq!(max_weight, 52);
     }
 
 
+    // The Bellman-Ford implementation the successive shortest path solver replaced, kept
+    // as the reference the new solver has to agree with.
+    fn bellman_ford_max_weight_matching(weights: &[Vec<i128>], k: usize) -> (i128, Vec<(usize, usize)>) {
+        let n = weights.len();
+        let m = weights[0].len();
+        let (start, end) = (n + m, n + m + 1);
+        let mut matched_left = vec![usize::MAX; n];
+        let mut matched_right = vec![usize::MAX; m];
+        let mut max_weight = 0;
+
+        for _ in 1..=k {
+            let mut g: Vec<Vec<(usize, i128)>> = vec![vec![]; n + m + 2];
+            for i in 0..n {
+                if matched_left[i] == usize::MAX {
+                    g[start].push((i, 0));
+                }
+                for (j, &w) in weights[i].iter().enumerate() {
+                    if matched_left[i] == j {
+                        g[j + n].push((i, w));
+                    } else {
+                        g[i].push((j + n, -w));
+                    }
+                }
+            }
+            for (j, &i) in matched_right.iter().enumerate() {
+                if i == usize::MAX {
+                    g[j + n].push((end, 0));
+                }
+            }
+
+            let mut distance = vec![i128::MAX; n + m + 2];
+            let mut predecessor = vec![usize::MAX; n + m + 2];
+            distance[start] = 0;
+            for _ in 0..n + m + 1 {
+                for u in 0..n + m + 2 {
+                    if distance[u] == i128::MAX {
+                        continue;
+                    }
+                    for &(v, w) in &g[u] {
+                        if distance[u] + w < distance[v] {
+                            distance[v] = distance[u] + w;
+                            predecessor[v] = u;
+                        }
+                    }
+                }
+            }
+            if distance[end] == i128::MAX {
+                break;
+            }
+
+            let mut v = predecessor[end];
+            while v != start {
+                let u = predecessor[v];
+                if u < n && v >= n {
+                    matched_left[u] = v - n;
+                    matched_right[v - n] = u;
+                    max_weight += weights[u][v - n];
+                } else if v < n && u >= n && u < start {
+                    max_weight -= weights[v][u - n];
+                }
+                v = u;
+            }
+        }
+
+        let matched_pairs = (0..n).filter(|&i| matched_left[i] != usize::MAX).map(|i| (i, matched_left[i])).collect();
+        (max_weight, matched_pairs)
+    }
+
+    #[test]
+    fn test_fixed_size_max_weight_matching_agrees_with_bellman_ford() {
+        use rand::Rng;
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..50 {
+            let n = rng.gen_range(1..8);
+            let m = rng.gen_range(n..12);
+            // Integer weights in a small range produce many ties, so only the weights are compared.
+            let weights: Vec<Vec<i128>> = (0..n).map(|_| (0..m).map(|_| rng.gen_range(0..10)).collect()).collect();
+            for k in 0..=n {
+                let (max_weight, matched) = fixed_size_max_weight_matching(&weights, k).unwrap();
+                let (expected_weight, _) = bellman_ford_max_weight_matching(&weights, k);
+                assert_eq!(max_weight, expected_weight);
+                assert_eq!(matched.len(), k);
+            }
+
+            // Weights drawn from a huge range have a unique optimum, so the matched sets agree too.
+            let weights: Vec<Vec<i128>> = (0..n).map(|_| (0..m).map(|_| rng.gen_range(0..1_000_000_000_000)).collect()).collect();
+            for k in 0..=n {
+                assert_eq!(fixed_size_max_weight_matching(&weights, k).unwrap(), bellman_ford_max_weight_matching(&weights, k));
+            }
+        }
+    }
+
+
+    #[test]
+    fn test_incremental_matching() {
+        use rand::Rng;
+
+        let n_each: usize = 4;
+        let num_items: usize = 12;
+        let mut rng = rand::thread_rng();
+        let preferences: Vec<Vec<f64>> = (0..n_each).map(|_| (0..num_items).map(|_| rng.gen()).collect()).collect();
+        let left_list: Vec<usize> = (0..n_each).collect();
+
+        let mut available: Vec<usize> = (0..8).collect();
+        let mut matching = IncrementalMatching::new(left_list.clone(), available.clone(), &preferences).unwrap();
+        for step in 0..40 {
+            match step % 4 {
+                0 | 1 if matching.size() < n_each => {
+                    let item = matching.augment().unwrap();
+                    assert!(available.contains(&item));
+                }
+                2 if available.len() > n_each => {
+                    // Alternates between withdrawing a matched and an unmatched item.
+                    let assignments = matching.assignments();
+                    let pos = rng.gen_range(0..available.len());
+                    let item = available.remove(pos);
+                    matching.remove_item(item);
+                    if assignments.contains(&item) {
+                        assert_eq!(matching.size(), assignments.len() - 1);
+                    } else {
+                        assert_eq!(matching.assignments(), assignments);
+                    }
+                }
+                _ => {
+                    let missing: Vec<usize> = (0..num_items).filter(|item| !available.contains(item)).collect();
+                    if let Some(&item) = missing.get(rng.gen_range(0..missing.len().max(1))) {
+                        available.push(item);
+                        matching.add_item(item, &preferences).unwrap();
+                    }
+                }
+            }
+
+            let (max_weight, assignments) = compute_max_weight_matching(left_list.clone(), available.clone(), &preferences, matching.size()).unwrap();
+            assert!((matching.weight() - max_weight).abs() < 1e-9);
+            assert_eq!(matching.assignments().len(), assignments.len());
+            assert!(matching.assignments().iter().all(|item| available.contains(item)));
+        }
+    }
+
+    #[test]
+    fn test_compute_max_weight_matching_exact_weights() {
+        use num_rational::Ratio;
+
+        // Weights below the old 1e-7 fixed-point resolution are no longer lost.
+        let preferences = vec![vec![0.3, 0.1], vec![0.1, 0.00000009]];
+        let (max_weight, _) = compute_max_weight_matching(vec![0, 1], vec![0, 1], &preferences, 2).unwrap();
+        assert_eq!(max_weight, 0.3 + 0.00000009);
+
+        let preferences: Vec<Vec<i64>> = vec![vec![3, 5, 1], vec![4, 6, 2]];
+        let (max_weight, _) = compute_max_weight_matching(vec![0, 1], vec![0, 1, 2], &preferences, 2).unwrap();
+        assert_eq!(max_weight, 9);
+
+        // 1/3 + 2/3 and 1/2 + 1/2 tie exactly, which no fixed-point scaling represents.
+        let third = |k: i64| Ratio::new(k, 3);
+        let half = |k: i64| Ratio::new(k, 2);
+        let preferences = vec![vec![third(1), half(1), Ratio::from_integer(0)], vec![half(1), third(2), Ratio::from_integer(0)]];
+        let (max_weight, _) = compute_max_weight_matching(vec![0, 1], vec![0, 1, 2], &preferences, 2).unwrap();
+        assert_eq!(max_weight, Ratio::from_integer(1));
+        let (max_weight, assignments) = compute_max_weight_matching(vec![0, 1], vec![0, 1, 2], &preferences, 1).unwrap();
+        assert_eq!(max_weight, third(2));
+        assert_eq!(assignments, HashSet::from([1]));
+    }
+
+    #[test]
+    fn test_compute_max_weight_assignment() {
+        let mut preferences: Vec<Vec<i64>> = vec![vec![0; 3]; 8];
+        preferences[5] = vec![10, 2, 3];
+        preferences[7] = vec![4, 15, 6];
+
+        let (max_weight, assignment) = compute_max_weight_assignment(vec![5, 7], vec![0, 1, 2], &preferences, 2).unwrap();
+        assert_eq!(max_weight, 25);
+        assert_eq!(assignment, HashMap::from([(5, (0, 10)), (7, (1, 15))]));
+
+        let (max_weight, assignment) = compute_max_weight_assignment(vec![5, 7], vec![0, 2], &preferences, 1).unwrap();
+        assert_eq!(max_weight, 10);
+        assert_eq!(assignment, HashMap::from([(5, (0, 10))]));
+
+        // More members than items.
+        let (max_weight, assignment) = compute_max_weight_assignment(vec![5, 7], vec![2], &preferences, 1).unwrap();
+        assert_eq!(max_weight, 6);
+        assert_eq!(assignment, HashMap::from([(7, (2, 6))]));
+    }
+
+    #[test]
+    fn test_compute_max_weight_matching_sparse() {
+        use crate::preferences::SparsePreferences;
+        use crate::solver::{BinarySolver, KuhnMunkresSolver};
+        use rand::Rng;
+
+        // Agent 1 values item 0 only, so a matching of two is not possible without a zero edge.
+        let dense: Vec<Vec<i64>> = vec![vec![3, 2, 0], vec![5, 0, 0]];
+        let sparse = SparsePreferences::from_dense(&dense);
+        assert_eq!(compute_max_weight_assignment(vec![0, 1], vec![0, 1, 2], &dense, 2).unwrap().0, 7);
+        let (max_weight, assignment) = compute_max_weight_assignment(vec![0, 1], vec![0, 1, 2], &sparse, 2).unwrap();
+        assert_eq!(max_weight, 7);
+        assert_eq!(assignment, HashMap::from([(0, (1, 2)), (1, (0, 5))]));
+        let (max_weight, assignment) = compute_max_weight_assignment(vec![0, 1], vec![1, 2], &sparse, 2).unwrap();
+        assert_eq!(max_weight, 2);
+        assert_eq!(assignment, HashMap::from([(0, (1, 2))]));
+
+        // Dense preferences without zeros give the same matching in both forms, and both
+        // solvers agree on random sparse graphs.
+        let mut rng = rand::thread_rng();
+        for _ in 0..30 {
+            let n = rng.gen_range(1..6);
+            let m = rng.gen_range(1..9);
+            let dense: Vec<Vec<i64>> = (0..n).map(|_| (0..m).map(|_| rng.gen_range(1..100)).collect()).collect();
+            let sparse = SparsePreferences::from_dense(&dense);
+            for k in 0..=n.min(m) {
+                let expected = compute_max_weight_matching((0..n).collect(), (0..m).collect(), &dense, k).unwrap();
+                assert_eq!(compute_max_weight_matching((0..n).collect(), (0..m).collect(), &sparse, k).unwrap().0, expected.0);
+            }
+
+            let approvals: Vec<Vec<i64>> = (0..n).map(|_| (0..m).map(|_| if rng.gen_bool(0.3) { rng.gen_range(1..10) } else { 0 }).collect()).collect();
+            let sparse = SparsePreferences::from_dense(&approvals);
+            for k in 0..=n.min(m) {
+                let (max_weight, assignment) = compute_max_weight_assignment((0..n).collect(), (0..m).collect(), &sparse, k).unwrap();
+                let (km_weight, km_assignment) = compute_max_weight_assignment_with(&KuhnMunkresSolver, (0..n).collect(), (0..m).collect(), &sparse, k).unwrap();
+                assert_eq!(max_weight, km_weight);
+                assert_eq!(assignment.len(), km_assignment.len());
+                assert!(assignment.values().all(|&(_, value)| value > 0));
+            }
+
+            // Successive shortest paths and the Hopcroft-Karp of `BinarySolver` agree on
+            // sparse 0/1 approvals.
+            let approvals: Vec<Vec<i64>> = (0..n).map(|_| (0..m).map(|_| rng.gen_range(0..2)).collect()).collect();
+            let sparse = SparsePreferences::from_dense(&approvals);
+            for k in 0..=n.min(m) {
+                let (max_weight, assignment) = compute_max_weight_assignment((0..n).collect(), (0..m).collect(), &sparse, k).unwrap();
+                let (binary_weight, binary_assignment) = compute_max_weight_assignment_with(&BinarySolver, (0..n).collect(), (0..m).collect(), &sparse, k).unwrap();
+                assert_eq!(max_weight, binary_weight);
+                assert_eq!(assignment.len(), binary_assignment.len());
+                assert!(binary_assignment.values().all(|&(_, value)| value == 1));
+            }
+        }
+    }
+
+    #[test]
+    fn test_compute_max_weight_matching_errors() {
+        let preferences: Vec<Vec<f64>> = vec![vec![1.0, 2.0, 3.0], vec![4.0, f64::NAN, 6.0]];
+        assert_eq!(
+            compute_max_weight_matching(vec![0, 1], vec![0, 2], &preferences, 3),
+            Err(MatchingError::InfeasibleSize { requested: 3, largest: 2 })
+        );
+        assert_eq!(
+            compute_max_weight_matching(vec![0, 1], vec![0, 1], &preferences, 1),
+            Err(MatchingError::NotANumber { agent: 1, item: 1 })
+        );
+        assert_eq!(
+            compute_max_weight_matching(vec![0, 2], vec![0], &preferences, 1),
+            Err(MatchingError::AgentOutOfRange { agent: 2, num_agents: 2 })
+        );
+        assert_eq!(
+            compute_max_weight_curve(vec![0], vec![3], &preferences, 1),
+            Err(MatchingError::ItemOutOfRange { agent: 0, item: 3, num_items: 3 })
+        );
+        assert!(IncrementalMatching::new(vec![0, 1], vec![0, 1], &preferences).is_err());
+
+        let mut matching = IncrementalMatching::new(vec![0, 1], vec![0], &preferences).unwrap();
+        assert_eq!(matching.add_item(1, &preferences), Err(MatchingError::NotANumber { agent: 1, item: 1 }));
+        assert_eq!(matching.add_item(2, &preferences), Ok(()));
+    }
+
+    #[test]
+    fn test_compute_max_weight_matching_signs() {
+        use crate::solver::KuhnMunkresSolver;
+        use rand::Rng;
+
+        // Goods: matching everybody is best.
+        let goods: Vec<Vec<i64>> = vec![vec![3, 1], vec![2, 4]];
+        assert_eq!(compute_max_weight_matching(vec![0, 1], vec![0, 1], &goods, 2).unwrap().0, 7);
+        assert_eq!(compute_max_weight_assignment_up_to(vec![0, 1], vec![0, 1], &goods, 2).unwrap(), (7, HashMap::from([(0, (0, 3)), (1, (1, 4))])));
+
+        // Chores: a matching of a given size does the cheapest chores, and with a choice
+        // nobody takes any.
+        let chores: Vec<Vec<i64>> = vec![vec![-3, -1], vec![-2, -4]];
+        assert_eq!(compute_max_weight_assignment(vec![0, 1], vec![0, 1], &chores, 2).unwrap(), (-3, HashMap::from([(0, (1, -1)), (1, (0, -2))])));
+        assert_eq!(compute_max_weight_assignment(vec![0, 1], vec![0, 1], &chores, 1).unwrap(), (-1, HashMap::from([(0, (1, -1))])));
+        assert_eq!(compute_max_weight_assignment_up_to(vec![0, 1], vec![0, 1], &chores, 2).unwrap(), (0, HashMap::new()));
+
+        // Mixed: member 1 only has chores left once member 0 takes the good, and is
+        // better off unmatched.
+        let mixed: Vec<Vec<i64>> = vec![vec![5, -1], vec![-2, -3]];
+        assert_eq!(compute_max_weight_matching(vec![0, 1], vec![0, 1], &mixed, 2).unwrap().0, 2);
+        assert_eq!(compute_max_weight_assignment_up_to(vec![0, 1], vec![0, 1], &mixed, 2).unwrap(), (5, HashMap::from([(0, (0, 5))])));
+
+        // Both solvers agree with the best size of the curve in every regime.
+        let mut rng = rand::thread_rng();
+        for range in [0..100, -100..0, -100..100] {
+            for _ in 0..20 {
+                let n = rng.gen_range(1..6);
+                let m = rng.gen_range(1..8);
+                let preferences: Vec<Vec<i64>> = (0..n).map(|_| (0..m).map(|_| rng.gen_range(range.clone())).collect()).collect();
+                let curve = compute_max_weight_curve((0..n).collect(), (0..m).collect(), &preferences, n).unwrap();
+                for k in 0..curve.len() {
+                    let expected = curve[..=k].iter().map(|(max_weight, _)| *max_weight).max().unwrap();
+                    let (max_weight, assignment) = compute_max_weight_assignment_up_to((0..n).collect(), (0..m).collect(), &preferences, k).unwrap();
+                    assert_eq!(max_weight, expected);
+                    assert!(assignment.len() <= k);
+                    assert_eq!(compute_max_weight_matching_up_to_with(&KuhnMunkresSolver, (0..n).collect(), (0..m).collect(), &preferences, k).unwrap().0, expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_compute_max_weight_b_matching() {
+        use crate::solver::KuhnMunkresSolver;
+        use rand::Rng;
+
+        // Two units of item 0 go to the two agents who value it most.
+        let preferences: Vec<Vec<i64>> = vec![vec![10, 1], vec![9, 2], vec![8, 3]];
+        let (max_weight, assignment) = compute_max_weight_b_matching(vec![0, 1, 2], vec![0, 1], &[2, 1], &preferences, 3).unwrap();
+        assert_eq!(max_weight, 22);
+        assert_eq!(assignment, HashMap::from([(0, (0, 10)), (1, (0, 9)), (2, (1, 3))]));
+        assert_eq!(compute_max_weight_b_matching(vec![0, 1, 2], vec![0, 1], &[2, 1], &preferences, 2).unwrap().0, 19);
+        assert_eq!(
+            compute_max_weight_b_matching(vec![0, 1, 2], vec![0, 1], &[1, 1], &preferences, 3),
+            Err(MatchingError::InfeasibleSize { requested: 3, largest: 2 })
+        );
+        assert_eq!(
+            compute_max_weight_b_matching(vec![0], vec![0, 1], &[1], &preferences, 1),
+            Err(MatchingError::MissingUnits { item: 1, num_items: 2 })
+        );
+        // Only item 1 is asked for, but `units` needs the entries of items 0 and 1.
+        assert_eq!(
+            compute_max_weight_b_matching(vec![0], vec![1], &[1], &preferences, 1),
+            Err(MatchingError::MissingUnits { item: 1, num_items: 2 })
+        );
+
+        // Withdrawing a matched unit solves the matching one size smaller.
+        let mut matching = IncrementalMatching::with_units(vec![0, 1, 2], vec![0, 1], &[2, 1], &preferences).unwrap();
+        assert_eq!((matching.augment(), matching.augment(), matching.augment()), (Some(0), Some(0), Some(1)));
+        assert_eq!(matching.weight(), 22);
+        matching.remove_item(0);
+        assert_eq!((matching.size(), matching.weight()), (2, 13));
+        matching.add_item(0, &preferences).unwrap();
+        assert_eq!((matching.size(), matching.weight()), (2, 19));
+
+        // Both solvers agree with copying every item once per unit.
+        let mut rng = rand::thread_rng();
+        for _ in 0..30 {
+            let n = rng.gen_range(1..7);
+            let m = rng.gen_range(1..5);
+            let units: Vec<usize> = (0..m).map(|_| rng.gen_range(0..4)).collect();
+            let preferences: Vec<Vec<i64>> = (0..n).map(|_| (0..m).map(|_| rng.gen_range(-50..100)).collect()).collect();
+            let copies: Vec<usize> = (0..m).flat_map(|item| std::iter::repeat_n(item, units[item])).collect();
+            let copied: Vec<Vec<i64>> = preferences.iter().map(|row| copies.iter().map(|&item| row[item]).collect()).collect();
+            for k in 0..=n.min(copies.len()) {
+                let expected = compute_max_weight_matching((0..n).collect(), (0..copies.len()).collect(), &copied, k).unwrap().0;
+                let (max_weight, assignment) = compute_max_weight_b_matching((0..n).collect(), (0..m).collect(), &units, &preferences, k).unwrap();
+                assert_eq!(max_weight, expected);
+                assert_eq!(assignment.len(), k);
+                assert!((0..m).all(|item| assignment.values().filter(|&&(used, _)| used == item).count() <= units[item]));
+                assert_eq!(compute_max_weight_b_matching_with(&KuhnMunkresSolver, (0..n).collect(), (0..m).collect(), &units, &preferences, k).unwrap().0, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_compute_max_weight_capacitated_matching() {
+        use crate::solver::Solver;
+        use rand::Rng;
+
+        // Agent 0 uses two items, so the group benefits from a third item.
+        let preferences: Vec<Vec<i64>> = vec![vec![10, 9, 1], vec![2, 3, 8]];
+        let (max_weight, assignment) = compute_max_weight_capacitated_matching(vec![0, 1], vec![0, 1, 2], &[2, 1], &[1, 1, 1], &preferences, 3).unwrap();
+        assert_eq!(max_weight, 27);
+        assert_eq!(assignment, MultiAssignment::from([(0, vec![(0, 10), (1, 9)]), (1, vec![(2, 8)])]));
+        assert_eq!(compute_max_weight_capacitated_matching(vec![0, 1], vec![0, 1, 2], &[1, 1], &[1, 1, 1], &preferences, 2).unwrap().0, 18);
+        assert_eq!(
+            compute_max_weight_capacitated_matching(vec![0, 1], vec![0, 1, 2], &[1, 1], &[1, 1, 1], &preferences, 3),
+            Err(MatchingError::InfeasibleSize { requested: 3, largest: 2 })
+        );
+        assert_eq!(
+            compute_max_weight_capacitated_matching(vec![0, 1], vec![0, 1, 2], &[2], &[1, 1, 1], &preferences, 1),
+            Err(MatchingError::MissingCapacity { agent: 1, num_agents: 1 })
+        );
+
+        // An agent never takes two units of the same item.
+        let preferences: Vec<Vec<i64>> = vec![vec![10, 1]];
+        for solver in [Solver::SuccessiveShortestPaths, Solver::KuhnMunkres] {
+            let (max_weight, assignment) = compute_max_weight_capacitated_matching_with(&solver, vec![0], vec![0, 1], &[2], &[2, 1], &preferences, 2).unwrap();
+            assert_eq!(max_weight, 11);
+            assert_eq!(assignment, MultiAssignment::from([(0, vec![(0, 10), (1, 1)])]));
+        }
+
+        let mut matching = IncrementalMatching::with_capacities(vec![0], vec![0, 1], &[2], &[2, 1], &preferences).unwrap();
+        assert_eq!((matching.augment(), matching.augment(), matching.augment()), (Some(0), Some(1), None));
+        assert_eq!(matching.multi_assignment(), MultiAssignment::from([(0, vec![(0, 10), (1, 1)])]));
+
+        // Both solvers agree with trying every set of edges.
+        let mut rng = rand::thread_rng();
+        for _ in 0..30 {
+            let n = rng.gen_range(1..4);
+            let m = rng.gen_range(1..4);
+            let capacities: Vec<usize> = (0..n).map(|_| rng.gen_range(0..3)).collect();
+            let units: Vec<usize> = (0..m).map(|_| rng.gen_range(0..3)).collect();
+            let preferences: Vec<Vec<i64>> = (0..n).map(|_| (0..m).map(|_| rng.gen_range(-50..100)).collect()).collect();
+            let mut best: Vec<Option<i64>> = vec![None; n * m + 1];
+            for subset in 0..1usize << (n * m) {
+                let used = |i: usize, j: usize| subset >> (i * m + j) & 1 == 1;
+                if (0..n).all(|i| (0..m).filter(|&j| used(i, j)).count() <= capacities[i]) && (0..m).all(|j| (0..n).filter(|&i| used(i, j)).count() <= units[j]) {
+                    let weight = (0..n).flat_map(|i| (0..m).map(move |j| (i, j))).filter(|&(i, j)| used(i, j)).map(|(i, j)| preferences[i][j]).sum();
+                    let size = subset.count_ones() as usize;
+                    best[size] = Some(best[size].map_or(weight, |b: i64| b.max(weight)));
+                }
+            }
+            for (k, expected) in best.into_iter().enumerate() {
+                let Some(expected) = expected else { continue };
+                for solver in [Solver::SuccessiveShortestPaths, Solver::KuhnMunkres] {
+                    let (max_weight, assignment) = compute_max_weight_capacitated_matching_with(&solver, (0..n).collect(), (0..m).collect(), &capacities, &units, &preferences, k).unwrap();
+                    assert_eq!(max_weight, expected);
+                    assert_eq!(assignment.values().map(Vec::len).sum::<usize>(), k);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_k_best_assignments() {
+        use crate::preferences::SparsePreferences;
+        use crate::solver::Solver;
+        use rand::Rng;
+
+        let preferences: Vec<Vec<i64>> = vec![vec![4, 1, 0], vec![3, 2, 5]];
+        let weights: Vec<i64> = k_best_assignments(vec![0, 1], vec![0, 1, 2], &preferences, 2).unwrap().map(|result| result.unwrap().0).collect();
+        assert_eq!(weights, vec![9, 6, 6, 4, 3, 2]);
+        let (_, best) = k_best_assignments(vec![0, 1], vec![0, 1, 2], &preferences, 2).unwrap().next().unwrap().unwrap();
+        assert_eq!(best, Assignment::from([(0, (0, 4)), (1, (2, 5))]));
+        assert_eq!(
+            k_best_assignments(vec![0, 1], vec![0], &preferences, 2).err(),
+            Some(MatchingError::InfeasibleSize { requested: 2, largest: 1 })
+        );
+
+        // Every matching of the size comes exactly once, in non-increasing order of weight.
+        let mut rng = rand::thread_rng();
+        for _ in 0..20 {
+            let n = rng.gen_range(1..5);
+            let m = rng.gen_range(1..5);
+            let rows: Vec<Vec<(usize, i64)>> = (0..n).map(|_| (0..m).map(|j| (j, rng.gen_range(-20..20), rng.gen_bool(0.7))).filter(|&(_, _, edge)| edge).map(|(j, w, _)| (j, w)).collect()).collect();
+            let preferences = SparsePreferences::new(rows.clone());
+            for k in 0..=n.min(m) {
+                // Brute force over one column, or none, for every row.
+                let mut expected: Vec<i64> = Vec::new();
+                let mut choice = vec![0; n];
+                loop {
+                    let pairs: Vec<(usize, usize)> = (0..n).filter(|&i| choice[i] > 0).map(|i| (i, choice[i] - 1)).collect();
+                    let columns: HashSet<usize> = pairs.iter().map(|&(_, j)| j).collect();
+                    if pairs.len() == k && columns.len() == k && pairs.iter().all(|&(i, j)| edge_weight(&rows[i], j).is_some()) {
+                        expected.push(pairs.iter().map(|&(i, j)| edge_weight(&rows[i], j).unwrap()).sum());
+                    }
+                    let Some(i) = (0..n).find(|&i| choice[i] < m) else { break };
+                    choice[i] += 1;
+                    choice[..i].iter_mut().for_each(|c| *c = 0);
+                }
+                expected.sort_by(|a, b| b.cmp(a));
+
+                for solver in [Solver::SuccessiveShortestPaths, Solver::KuhnMunkres] {
+                    let found: Vec<(i64, Assignment<i64>)> =
+                        k_best_assignments_with(&solver, (0..n).collect(), (0..m).collect(), &preferences, k).unwrap().map(Result::unwrap).collect();
+                    assert_eq!(found.iter().map(|&(w, _)| w).collect::<Vec<i64>>(), expected);
+                    let distinct: HashSet<Vec<(usize, usize)>> = found
+                        .iter()
+                        .map(|(_, assignment)| {
+                            let mut pairs: Vec<(usize, usize)> = assignment.iter().map(|(&agent, &(item, _))| (agent, item)).collect();
+                            pairs.sort();
+                            pairs
+                        })
+                        .collect();
+                    assert_eq!(distinct.len(), found.len());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_sensitivity() {
+        use crate::preferences::SparsePreferences;
+        use rand::Rng;
+
+        let preferences: Vec<Vec<i64>> = vec![vec![4, 1, 0], vec![3, 2, 5]];
+        let mut sensitivity = compute_max_weight_sensitivity(vec![0, 1], vec![0, 1, 2], &preferences, 2).unwrap();
+        assert_eq!(sensitivity.weight(), 9);
+        // Matched (0, 0) drops to 0: (0, 1) and (1, 2) are worth 6.
+        assert_eq!(sensitivity.weight_if(0, 0, 0), Some(6));
+        assert_eq!(sensitivity.weight_if(0, 0, 10), Some(15));
+        // Unmatched (1, 1) rises to 8: (0, 0) and (1, 1) are worth 12.
+        assert_eq!(sensitivity.weight_if(1, 1, 8), Some(12));
+        assert_eq!(sensitivity.weight_if(1, 1, 3), Some(9));
+        assert_eq!(sensitivity.weight_if(2, 0, 3), None);
+        assert_eq!(sensitivity.weight_if(0, 3, 3), None);
+
+        // Every query agrees with solving the changed instance again.
+        let mut rng = rand::thread_rng();
+        for range in [0..50, -50..0, -50..50] {
+            for _ in 0..10 {
+                let n = rng.gen_range(1..5);
+                let m = rng.gen_range(1..6);
+                let k = rng.gen_range(0..=n.min(m));
+                let preferences: Vec<Vec<i64>> = (0..n).map(|_| (0..m).map(|_| rng.gen_range(range.clone())).collect()).collect();
+                let rows: Vec<Vec<(usize, i64)>> = preferences.iter().map(|row| row.iter().copied().enumerate().filter(|&(_, w)| w % 3 != 0).collect()).collect();
+
+                let mut dense = compute_max_weight_sensitivity((0..n).collect(), (0..m).collect(), &preferences, k).unwrap();
+                let mut sparse = compute_max_weight_sensitivity((0..n).collect(), (0..m).collect(), &SparsePreferences::new(rows.clone()), k).unwrap();
+                let sparse_size = sparse.matching.size();
+                for agent in 0..n {
+                    for item in 0..m {
+                        for value in [0, rng.gen_range(range.clone()), 100, -100] {
+                            let mut changed = preferences.clone();
+                            changed[agent][item] = value;
+                            let expected = compute_max_weight_matching((0..n).collect(), (0..m).collect(), &changed, k).unwrap().0;
+                            assert_eq!(dense.weight_if(agent, item, value), Some(expected));
+
+                            let mut changed = rows.clone();
+                            changed[agent].retain(|&(j, _)| j != item);
+                            changed[agent].push((item, value));
+                            changed[agent].sort();
+                            let expected = compute_max_weight_matching((0..n).collect(), (0..m).collect(), &SparsePreferences::new(changed), sparse_size).unwrap().0;
+                            assert_eq!(sparse.weight_if(agent, item, value), Some(expected));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_tie_break() {
+        use rand::Rng;
+
+        // Every matching of two items has the same weight.
+        let preferences: Vec<Vec<i64>> = vec![vec![1; 4], vec![1; 4]];
+        let items = |tie_break: &TieBreak| {
+            let (max_weight, assignment) = compute_max_weight_assignment_with_tie_break(vec![0, 1], vec![0, 1, 2, 3], &preferences, 2, tie_break).unwrap();
+            assert_eq!(max_weight, 2);
+            let mut items: Vec<usize> = assignment.values().map(|&(item, _)| item).collect();
+            items.sort();
+            items
+        };
+        assert_eq!(items(&TieBreak::SmallestItems), vec![0, 1]);
+        assert_eq!(items(&TieBreak::LowestDemand(vec![5, 0, 3, 1])), vec![1, 3]);
+        let random = TieBreak::Random(42);
+        let mut expected: Vec<usize> = (0..4).collect();
+        expected.sort_by_key(|&item| random.key(item));
+        expected.truncate(2);
+        expected.sort();
+        assert_eq!(items(&random), expected);
+
+        // The preferred items of every optimal matching, found by trying every set of items.
+        let mut rng = rand::thread_rng();
+        for _ in 0..30 {
+            let n = rng.gen_range(1..4);
+            let m = rng.gen_range(1..7);
+            let preferences: Vec<Vec<i64>> = (0..n).map(|_| (0..m).map(|_| rng.gen_range(-1..3)).collect()).collect();
+            let demand: Vec<usize> = (0..m).map(|_| rng.gen_range(0..3)).collect();
+            for tie_break in [TieBreak::SmallestItems, TieBreak::LowestDemand(demand), TieBreak::Random(rng.gen())] {
+                for k in 0..=n.min(m) {
+                    let sorted_keys = |items: &[usize]| {
+                        let mut keys: Vec<(u64, usize)> = items.iter().filter_map(|&item| tie_break.key(item)).collect();
+                        keys.sort();
+                        keys
+                    };
+                    let optimum = compute_max_weight_matching((0..n).collect(), (0..m).collect(), &preferences, k).unwrap().0;
+                    let expected = (0..1usize << m)
+                        .map(|subset| (0..m).filter(|&item| subset >> item & 1 == 1).collect::<Vec<usize>>())
+                        .filter(|items| items.len() == k && compute_max_weight_matching((0..n).collect(), items.clone(), &preferences, k).unwrap().0 == optimum)
+                        .map(|items| sorted_keys(&items))
+                        .min()
+                        .unwrap();
+                    let (max_weight, assignment) = compute_max_weight_assignment_with_tie_break((0..n).collect(), (0..m).collect(), &preferences, k, &tie_break).unwrap();
+                    let items: Vec<usize> = assignment.values().map(|&(item, _)| item).collect();
+                    assert_eq!(max_weight, optimum);
+                    assert_eq!(sorted_keys(&items), expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_certificate() {
+        use crate::preferences::SparsePreferences;
+        use rand::Rng;
+
+        let preferences: Vec<Vec<i64>> = vec![vec![3, 1, 0], vec![2, 4, -1]];
+        let (max_weight, assignment, certificate) = compute_certified_max_weight_assignment(vec![0, 1], vec![0, 1, 2], &preferences, 2).unwrap();
+        assert_eq!(max_weight, 7);
+        assert_eq!(verify_certificate(&[0, 1], &[0, 1, 2], &preferences, 2, &assignment, &certificate), Ok(()));
+        // The dual objective equals the weight of the matching.
+        let objective = certificate.agent_duals.values().sum::<i64>() + certificate.item_duals.values().sum::<i64>() + 2 * certificate.size_dual;
+        assert_eq!(objective, 7);
+
+        // A worse matching, or duals that no longer cover every edge, fail the check.
+        let worse = Assignment::from([(0, (1, 1)), (1, (0, 2))]);
+        assert!(matches!(verify_certificate(&[0, 1], &[0, 1, 2], &preferences, 2, &worse, &certificate), Err(MatchingError::InvalidCertificate(_))));
+        let mut lowered = certificate.clone();
+        lowered.size_dual -= 1;
+        assert!(matches!(verify_certificate(&[0, 1], &[0, 1, 2], &preferences, 2, &assignment, &lowered), Err(MatchingError::InvalidCertificate(_))));
+        assert!(matches!(verify_certificate(&[0, 1], &[0, 1, 2], &preferences, 1, &assignment, &certificate), Err(MatchingError::InvalidCertificate(_))));
+        assert_eq!(
+            compute_certified_max_weight_assignment(vec![0, 1], vec![0], &preferences, 2),
+            Err(MatchingError::InfeasibleSize { requested: 2, largest: 1 })
+        );
+
+        // Goods, chores and mixed values, dense and sparse, before and after items are withdrawn.
+        let mut rng = rand::thread_rng();
+        for range in [0.0..1.0, -1.0..0.0, -1.0..1.0] {
+            for _ in 0..20 {
+                let n = rng.gen_range(1..6);
+                let m = rng.gen_range(1..8);
+                let (left, right): (Vec<usize>, Vec<usize>) = ((0..n).collect(), (0..m).collect());
+                let preferences: Vec<Vec<f64>> = (0..n).map(|_| (0..m).map(|_| rng.gen_range(range.clone())).collect()).collect();
+                for k in 0..=n.min(m) {
+                    let (max_weight, assignment, certificate) = compute_certified_max_weight_assignment(left.clone(), right.clone(), &preferences, k).unwrap();
+                    assert_eq!(verify_certificate(&left, &right, &preferences, k, &assignment, &certificate), Ok(()));
+                    assert!((max_weight - compute_max_weight_matching(left.clone(), right.clone(), &preferences, k).unwrap().0).abs() < 1e-9);
+                }
+
+                let sparse = SparsePreferences::new(preferences.iter().map(|row| row.iter().copied().enumerate().filter(|_| rng.gen_bool(0.5)).collect()).collect());
+                let mut matching = IncrementalMatching::new(left.clone(), right.clone(), &sparse).unwrap();
+                while matching.augment().is_some() {
+                    assert_eq!(verify_certificate(&left, &right, &sparse, matching.size(), &matching.assignment(), &matching.certificate()), Ok(()));
+                }
+                matching.remove_item(rng.gen_range(0..m));
+                let remaining: Vec<usize> = right.iter().copied().filter(|&item| matching.ssp.units(item).0 > 0).collect();
+                assert_eq!(verify_certificate(&left, &remaining, &sparse, matching.size(), &matching.assignment(), &matching.certificate()), Ok(()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_compute_max_weight_curve() {
+        use crate::preferences::SparsePreferences;
+        use crate::solver::KuhnMunkresSolver;
+        use rand::Rng;
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..20 {
+            let n = rng.gen_range(1..6);
+            let m = rng.gen_range(n..9);
+            let preferences: Vec<Vec<i64>> = (0..n).map(|_| (0..m).map(|_| rng.gen_range(0..1_000_000)).collect()).collect();
+            let curve = compute_max_weight_curve((0..n).collect(), (0..m).collect(), &preferences, n).unwrap();
+            assert_eq!(curve.len(), n + 1);
+            for (size, entry) in curve.iter().enumerate() {
+                assert_eq!(*entry, compute_max_weight_matching((0..n).collect(), (0..m).collect(), &preferences, size).unwrap());
+            }
+            assert_eq!(compute_max_weight_curve_with(&KuhnMunkresSolver, (0..n).collect(), (0..m).collect(), &preferences, n).unwrap(), curve);
+        }
+
+        // The curve stops at the largest matching of a sparse graph.
+        let preferences = SparsePreferences::new(vec![vec![(0, 2)], vec![(0, 3)], vec![(1, 1)]]);
+        let curve = compute_max_weight_curve(vec![0, 1, 2], vec![0, 1, 2], &preferences, 3).unwrap();
+        assert_eq!(curve, vec![(0, HashSet::new()), (3, HashSet::from([0])), (4, HashSet::from([0, 1]))]);
+        assert_eq!(compute_max_weight_curve_with(&KuhnMunkresSolver, vec![0, 1, 2], vec![0, 1, 2], &preferences, 3).unwrap(), curve);
+    }
+
     #[test]
     fn test_compute_max_weight_matching() {
         use rand::Rng;
@@ -240,7 +1871,7 @@ mod tests {
 
         let match_size = 1;
 
-        let (max_weight, assignments) = compute_max_weight_matching((0..n_each).collect(), (50..num_items).collect(), &preferences, match_size);
+        let (max_weight, assignments) = compute_max_weight_matching((0..n_each).collect(), (50..num_items).collect(), &preferences, match_size).unwrap();
 
         println!("max_weight: {:?}", max_weight);
         println!("assignments: {:?}", assignments);
@@ -0,0 +1,249 @@
+use crate::error::MatchingError;
+use crate::tie_break::mix;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::str::FromStr;
+
+
+/// Order in which the groups pick during round robin, one round after another.
+///
+/// A group that already holds its share, or finds nothing left, skips its turn.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum PickingSequence {
+    /// Groups `0..num_groups` every round.
+    #[default]
+    RoundRobin,
+    /// The given groups, in this order, every round; a group may appear several
+    /// times or not at all.
+    Explicit(Vec<usize>),
+    /// Round `r` in order when the Thue-Morse bit of `r` is zero and reversed
+    /// otherwise, i.e. ABBA BAAB for two groups.
+    Balanced,
+    /// Every other round reversed, i.e. ABBA ABBA for two groups.
+    Snake,
+    /// A random permutation drawn from the seed and the round.
+    Random(u64),
+    /// Picks in proportion to the entitlements of the groups: the next pick goes
+    /// to the group of the largest `entitlement / (picks + shift)`, the first on
+    /// ties. A round lasts until every group has picked once.
+    ///
+    /// A shift of zero is the weighted picking sequence, the smallest
+    /// `picks / entitlement` first, which gives weighted EF1 allocations for
+    /// additive valuations; a shift of one favours the groups of large
+    /// entitlements, and one half splits the difference. Equal entitlements give
+    /// round robin whatever the shift.
+    Weighted { entitlements: Vec<f64>, shift: f64 },
+}
+
+impl PickingSequence {
+    /// Groups in the order they pick in round `round`.
+    pub fn order(&self, round: usize, num_groups: usize) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..num_groups).collect();
+        match self {
+            PickingSequence::RoundRobin => {}
+            PickingSequence::Explicit(groups) => order = groups.clone(),
+            PickingSequence::Balanced => {
+                if round.count_ones() % 2 == 1 {
+                    order.reverse();
+                }
+            }
+            PickingSequence::Snake => {
+                if round % 2 == 1 {
+                    order.reverse();
+                }
+            }
+            // The round is mixed in rather than added, or seed s + 1 would replay seed s
+            // one round late.
+            PickingSequence::Random(seed) => order.shuffle(&mut StdRng::seed_from_u64(mix(seed ^ mix(0, round), 0))),
+            // Replayed from the first round; `rounds` keeps the picks instead.
+            PickingSequence::Weighted { entitlements, shift } => {
+                let mut picks = vec![0.0; num_groups];
+                for _ in 0..round {
+                    weighted_round(entitlements, *shift, &mut picks);
+                }
+                order = weighted_round(entitlements, *shift, &mut picks);
+            }
+        }
+        order
+    }
+
+    /// Orders of the rounds `0, 1, ...` in turn, as `order` gives them, with the picks
+    /// of a weighted sequence kept from one round to the next.
+    pub fn rounds(&self, num_groups: usize) -> Rounds<'_> {
+        Rounds { sequence: self, num_groups, round: 0, picks: vec![0.0; num_groups] }
+    }
+
+    /// Checks that an explicit sequence is not empty and only names groups
+    /// `0..num_groups`.
+    pub fn validate(&self, num_groups: usize) -> Result<(), MatchingError> {
+        if let PickingSequence::Explicit(groups) = self {
+            if groups.is_empty() {
+                return Err(MatchingError::EmptyPickingSequence);
+            }
+            if let Some(&group) = groups.iter().find(|&&group| group >= num_groups) {
+                return Err(MatchingError::GroupOutOfRange { group, num_groups });
+            }
+        }
+        if let PickingSequence::Weighted { entitlements, shift } = self {
+            if entitlements.len() < num_groups {
+                return Err(MatchingError::MissingEntitlements { num_groups, found: entitlements.len() });
+            }
+            if let Some(group) = entitlements.iter().take(num_groups).position(|&entitlement| !(entitlement > 0.0 && entitlement.is_finite())) {
+                return Err(MatchingError::InvalidEntitlement { group });
+            }
+            if !(0.0..=1.0).contains(shift) {
+                return Err(MatchingError::InvalidShift);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Iterator of `PickingSequence::rounds`, which never ends.
+#[derive(Clone, Debug)]
+pub struct Rounds<'a> {
+    sequence: &'a PickingSequence,
+    num_groups: usize,
+    round: usize,
+    /// Picks of every group so far, for a weighted sequence.
+    picks: Vec<f64>,
+}
+
+impl Iterator for Rounds<'_> {
+    type Item = Vec<usize>;
+
+    fn next(&mut self) -> Option<Vec<usize>> {
+        let order = match self.sequence {
+            PickingSequence::Weighted { entitlements, shift } => weighted_round(entitlements, *shift, &mut self.picks),
+            sequence => sequence.order(self.round, self.num_groups),
+        };
+        self.round += 1;
+        Some(order)
+    }
+}
+
+/// Next round of a weighted sequence after the `picks` of every group so far, which
+/// it adds to. Groups without a positive entitlement never pick.
+fn weighted_round(entitlements: &[f64], shift: f64, picks: &mut [f64]) -> Vec<usize> {
+    let entitled: Vec<usize> = (0..picks.len()).filter(|&p| entitlements.get(p).is_some_and(|&entitlement| entitlement > 0.0)).collect();
+    let mut order = Vec::new();
+    let mut waiting = entitled.len();
+    let mut picked = vec![false; picks.len()];
+    while waiting > 0 {
+        // entitlement[p] / (picks[p] + shift) > entitlement[q] / (picks[q] + shift),
+        // multiplied out so that groups yet to pick at a shift of zero come first.
+        let next = entitled.iter().copied().fold(entitled[0], |best, p| {
+            if entitlements[p] * (picks[best] + shift) > entitlements[best] * (picks[p] + shift) { p } else { best }
+        });
+        picks[next] += 1.0;
+        if !picked[next] {
+            picked[next] = true;
+            waiting -= 1;
+        }
+        order.push(next);
+    }
+    order
+}
+
+impl FromStr for PickingSequence {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "round-robin" | "rr" => Ok(PickingSequence::RoundRobin),
+            "balanced" | "abba" => Ok(PickingSequence::Balanced),
+            "snake" => Ok(PickingSequence::Snake),
+            _ if s.starts_with("weighted:") || s.starts_with("divisor:") => parse_weighted(s),
+            _ => match s.strip_prefix("random:") {
+                Some(seed) => seed.parse().map(PickingSequence::Random).map_err(|_| "invalid seed for random sequence"),
+                None => s
+                    .split(',')
+                    .map(|group| group.trim().parse())
+                    .collect::<Result<Vec<usize>, _>>()
+                    .map(PickingSequence::Explicit)
+                    .map_err(|_| "invalid value for sequence"),
+            },
+        }
+    }
+}
+
+/// `weighted:<entitlements>` with a shift of zero, or `divisor:<shift>:<entitlements>`,
+/// the entitlements separated by commas.
+fn parse_weighted(s: &str) -> Result<PickingSequence, &'static str> {
+    let (shift, entitlements) = match s.strip_prefix("weighted:") {
+        Some(entitlements) => (0.0, entitlements),
+        None => {
+            let rest = s.strip_prefix("divisor:").unwrap_or(s);
+            let (shift, entitlements) = rest.split_once(':').ok_or("invalid value for sequence")?;
+            (shift.parse().map_err(|_| "invalid shift for weighted sequence")?, entitlements)
+        }
+    };
+    let entitlements = entitlements
+        .split(',')
+        .map(|entitlement| entitlement.trim().parse())
+        .collect::<Result<Vec<f64>, _>>()
+        .map_err(|_| "invalid entitlements for weighted sequence")?;
+    Ok(PickingSequence::Weighted { entitlements, shift })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_picking_sequence() {
+        let rounds = |sequence: &PickingSequence| (0..4).flat_map(|round| sequence.order(round, 2)).collect::<Vec<usize>>();
+        assert_eq!(rounds(&PickingSequence::RoundRobin), vec![0, 1, 0, 1, 0, 1, 0, 1]);
+        assert_eq!(rounds(&PickingSequence::Balanced), vec![0, 1, 1, 0, 1, 0, 0, 1]);
+        assert_eq!(rounds(&PickingSequence::Snake), vec![0, 1, 1, 0, 0, 1, 1, 0]);
+        assert_eq!(rounds(&PickingSequence::Explicit(vec![1, 0, 0])), vec![1, 0, 0, 1, 0, 0, 1, 0, 0, 1, 0, 0]);
+
+        let random = PickingSequence::Random(7);
+        assert_eq!(random.order(3, 5), PickingSequence::Random(7).order(3, 5));
+        let mut order = random.order(3, 5);
+        order.sort_unstable();
+        assert_eq!(order, vec![0, 1, 2, 3, 4]);
+        // The next seed is not the same sequence one round late.
+        let late: Vec<Vec<usize>> = (1..6).map(|round| PickingSequence::Random(7).order(round, 8)).collect();
+        let next: Vec<Vec<usize>> = (0..5).map(|round| PickingSequence::Random(8).order(round, 8)).collect();
+        assert_ne!(late, next);
+
+        assert_eq!("snake".parse(), Ok(PickingSequence::Snake));
+        assert_eq!("random:3".parse(), Ok(PickingSequence::Random(3)));
+        assert_eq!("0,1,1,0".parse(), Ok(PickingSequence::Explicit(vec![0, 1, 1, 0])));
+        assert!("0,x".parse::<PickingSequence>().is_err());
+
+        assert_eq!(PickingSequence::Explicit(vec![]).validate(2), Err(MatchingError::EmptyPickingSequence));
+        assert_eq!(PickingSequence::Explicit(vec![0, 2]).validate(2), Err(MatchingError::GroupOutOfRange { group: 2, num_groups: 2 }));
+        assert_eq!(PickingSequence::Balanced.validate(2), Ok(()));
+    }
+
+    #[test]
+    fn test_weighted_picking_sequence() {
+        let weighted = |entitlements: Vec<f64>, shift: f64| PickingSequence::Weighted { entitlements, shift };
+        let rounds = |sequence: &PickingSequence| (0..3).flat_map(|round| sequence.order(round, 2)).collect::<Vec<usize>>();
+        assert_eq!(rounds(&weighted(vec![1.0, 1.0], 1.0)), vec![0, 1, 0, 1, 0, 1]);
+        assert_eq!(rounds(&weighted(vec![1.0, 1.0], 0.0)), vec![0, 1, 0, 1, 0, 1]);
+        // Twice the entitlement, twice the picks; a shift of zero lets group 1 pick
+        // before group 0 picks again.
+        assert_eq!(rounds(&weighted(vec![2.0, 1.0], 0.0)), vec![0, 1, 0, 0, 1, 0, 0, 1]);
+        assert_eq!(rounds(&weighted(vec![2.0, 1.0], 1.0)), vec![0, 0, 1, 0, 0, 1, 0, 0, 1]);
+        assert_eq!(weighted(vec![1.0, 3.0, 1.0], 1.0).order(0, 3), vec![1, 1, 0, 1, 2]);
+        // The rounds carry the picks over, and agree with replaying every round.
+        for sequence in [weighted(vec![2.0, 3.0, 5.0], 0.0), weighted(vec![1.0, 4.0, 2.0], 0.5), PickingSequence::Random(3)] {
+            let replayed: Vec<Vec<usize>> = (0..6).map(|round| sequence.order(round, 3)).collect();
+            assert_eq!(sequence.rounds(3).take(6).collect::<Vec<_>>(), replayed);
+        }
+
+        assert_eq!("weighted:2,1".parse(), Ok(weighted(vec![2.0, 1.0], 0.0)));
+        assert_eq!("divisor:0.5:1,3".parse(), Ok(weighted(vec![1.0, 3.0], 0.5)));
+        assert!("divisor:1,3".parse::<PickingSequence>().is_err());
+
+        assert_eq!(weighted(vec![1.0], 1.0).validate(2), Err(MatchingError::MissingEntitlements { num_groups: 2, found: 1 }));
+        assert_eq!(weighted(vec![1.0, 0.0], 1.0).validate(2), Err(MatchingError::InvalidEntitlement { group: 1 }));
+        assert_eq!(weighted(vec![1.0, 2.0], 2.0).validate(2), Err(MatchingError::InvalidShift));
+        assert_eq!(weighted(vec![1.0, 2.0], 0.5).validate(2), Ok(()));
+    }
+}
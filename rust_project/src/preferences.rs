@@ -0,0 +1,177 @@
+use crate::error::MatchingError;
+use crate::weight::Weight;
+use std::collections::HashMap;
+
+
+/// Values agents have for items, seen as the edges of a bipartite graph.
+///
+/// Dense preferences (`Vec<Vec<W>>`, one value for every agent and item) form a
+/// complete graph, zero values included. `SparsePreferences` only has the edges it
+/// lists.
+pub trait Preferences<W: Weight> {
+    /// Value of `item` for `agent`, or `None` when the pair is not an edge.
+    fn value(&self, agent: usize, item: usize) -> Option<W>;
+
+    /// Edges from `agent` to the items of `columns`, which maps item ids to column
+    /// indices, as `(column, value)` pairs in increasing column order.
+    fn edges(&self, agent: usize, columns: &HashMap<usize, usize>) -> Vec<(usize, W)>;
+
+    /// Checks that every agent of `left_list` has preferences for the items of
+    /// `right_list` and that none of them is NaN.
+    fn validate(&self, left_list: &[usize], right_list: &[usize]) -> Result<(), MatchingError>;
+}
+
+impl<W: Weight> Preferences<W> for [Vec<W>] {
+    fn value(&self, agent: usize, item: usize) -> Option<W> {
+        Some(self[agent][item])
+    }
+
+    fn edges(&self, agent: usize, columns: &HashMap<usize, usize>) -> Vec<(usize, W)> {
+        let mut edges: Vec<(usize, W)> = columns.iter().map(|(&item, &j)| (j, self[agent][item])).collect();
+        edges.sort_by_key(|&(j, _)| j);
+        edges
+    }
+
+    fn validate(&self, left_list: &[usize], right_list: &[usize]) -> Result<(), MatchingError> {
+        let expected = self.first().map_or(0, Vec::len);
+        if let Some((agent, row)) = self.iter().enumerate().find(|(_, row)| row.len() != expected) {
+            return Err(MatchingError::RaggedPreferences { agent, len: row.len(), expected });
+        }
+        for &agent in left_list {
+            let row = self.get(agent).ok_or(MatchingError::AgentOutOfRange { agent, num_agents: self.len() })?;
+            for &item in right_list {
+                let value = row.get(item).ok_or(MatchingError::ItemOutOfRange { agent, item, num_items: row.len() })?;
+                if value.is_nan() {
+                    return Err(MatchingError::NotANumber { agent, item });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<W: Weight> Preferences<W> for Vec<Vec<W>> {
+    fn value(&self, agent: usize, item: usize) -> Option<W> {
+        self.as_slice().value(agent, item)
+    }
+
+    fn edges(&self, agent: usize, columns: &HashMap<usize, usize>) -> Vec<(usize, W)> {
+        self.as_slice().edges(agent, columns)
+    }
+
+    fn validate(&self, left_list: &[usize], right_list: &[usize]) -> Result<(), MatchingError> {
+        self.as_slice().validate(left_list, right_list)
+    }
+}
+
+/// Preferences given as a list of `(item, value)` pairs per agent.
+///
+/// Items missing from an agent's list are not edges, so the agent is never matched
+/// to them, and memory and solving time grow with the number of listed pairs only.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SparsePreferences<W> {
+    rows: Vec<Vec<(usize, W)>>,
+}
+
+impl<W: Weight> SparsePreferences<W> {
+    pub fn new(mut rows: Vec<Vec<(usize, W)>>) -> Self {
+        for row in rows.iter_mut() {
+            row.sort_by_key(|&(item, _)| item);
+            row.dedup_by_key(|&mut (item, _)| item);
+        }
+        SparsePreferences { rows }
+    }
+
+    /// Keeps the non-zero entries of dense preferences, so zero values become non-edges.
+    pub fn from_dense(preferences: &[Vec<W>]) -> Self {
+        let rows = preferences
+            .iter()
+            .map(|row| row.iter().enumerate().filter(|(_, w)| !w.is_zero()).map(|(item, &w)| (item, w)).collect())
+            .collect();
+        SparsePreferences { rows }
+    }
+
+    /// Listed `(item, value)` pairs of `agent`, in increasing item order.
+    pub fn row(&self, agent: usize) -> &[(usize, W)] {
+        &self.rows[agent]
+    }
+
+    /// Number of agents.
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Total number of listed pairs.
+    pub fn num_edges(&self) -> usize {
+        self.rows.iter().map(Vec::len).sum()
+    }
+}
+
+impl<W: Weight> Preferences<W> for SparsePreferences<W> {
+    fn value(&self, agent: usize, item: usize) -> Option<W> {
+        let row = &self.rows[agent];
+        row.binary_search_by_key(&item, |&(item, _)| item).ok().map(|pos| row[pos].1)
+    }
+
+    fn edges(&self, agent: usize, columns: &HashMap<usize, usize>) -> Vec<(usize, W)> {
+        let mut edges: Vec<(usize, W)> = self.rows[agent]
+            .iter()
+            .filter_map(|&(item, w)| Some((*columns.get(&item)?, w)))
+            .collect();
+        edges.sort_by_key(|&(j, _)| j);
+        edges
+    }
+
+    // Items missing from a row are non-edges rather than errors, so only the agents
+    // and the listed values are checked.
+    fn validate(&self, left_list: &[usize], _right_list: &[usize]) -> Result<(), MatchingError> {
+        for &agent in left_list {
+            let row = self.rows.get(agent).ok_or(MatchingError::AgentOutOfRange { agent, num_agents: self.rows.len() })?;
+            if let Some(&(item, _)) = row.iter().find(|(_, w)| w.is_nan()) {
+                return Err(MatchingError::NotANumber { agent, item });
+            }
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sparse_preferences() {
+        let dense = vec![vec![0.0, 0.5, 0.0], vec![0.25, 0.0, 1.0]];
+        let sparse = SparsePreferences::from_dense(&dense);
+        assert_eq!(sparse, SparsePreferences::new(vec![vec![(1, 0.5)], vec![(2, 1.0), (0, 0.25)]]));
+        assert_eq!(sparse.num_edges(), 3);
+        assert_eq!(sparse.value(0, 1), Some(0.5));
+        assert_eq!(sparse.value(0, 0), None);
+        assert_eq!(dense.value(0, 0), Some(0.0));
+
+        let columns = HashMap::from([(2, 0), (0, 1)]);
+        assert_eq!(sparse.edges(1, &columns), vec![(0, 1.0), (1, 0.25)]);
+        assert_eq!(dense.edges(0, &columns), vec![(0, 0.0), (1, 0.0)]);
+    }
+
+    #[test]
+    fn test_validate() {
+        let dense = vec![vec![0.0, 0.5, f64::NAN], vec![0.25, 0.0, 1.0]];
+        assert_eq!(dense.validate(&[0, 1], &[0, 1]), Ok(()));
+        assert_eq!(dense.validate(&[0, 1], &[1, 2]), Err(MatchingError::NotANumber { agent: 0, item: 2 }));
+        assert_eq!(dense.validate(&[2], &[0]), Err(MatchingError::AgentOutOfRange { agent: 2, num_agents: 2 }));
+        assert_eq!(dense.validate(&[1], &[3]), Err(MatchingError::ItemOutOfRange { agent: 1, item: 3, num_items: 3 }));
+
+        let ragged = vec![vec![1, 2, 3], vec![4, 5]];
+        assert_eq!(ragged.validate(&[0], &[0]), Err(MatchingError::RaggedPreferences { agent: 1, len: 2, expected: 3 }));
+
+        let sparse = SparsePreferences::new(vec![vec![(7, 1.0)], vec![(3, f64::NAN)]]);
+        assert_eq!(sparse.validate(&[0], &[0, 1]), Ok(()));
+        assert_eq!(sparse.validate(&[1], &[0]), Err(MatchingError::NotANumber { agent: 1, item: 3 }));
+    }
+}
@@ -0,0 +1,591 @@
+use crate::auction::auction_fixed_size_max_weight_matching;
+use crate::binary::{binary_fixed_size_max_weight_matching, is_binary};
+use crate::error::MatchingError;
+use crate::matching::{
+    check_b_matching, check_capacitated, check_dense, check_sparse, fixed_size_max_weight_matching, sparse_fixed_size_max_weight_b_matching,
+    sparse_fixed_size_max_weight_capacitated_matching, sparse_fixed_size_max_weight_matching, sparse_max_weight_matching_curve,
+};
+use crate::weight::Weight;
+use num_traits::{Bounded, Num, One, Signed, Zero};
+use pathfinding::kuhn_munkres::kuhn_munkres;
+use pathfinding::matrix::Matrix;
+use std::cmp::Ordering;
+use std::iter::Sum;
+use std::ops::{Add, Div, Mul, Neg, Rem, Sub};
+use std::str::FromStr;
+
+
+/// Weight of a matching and its matched `(row, column)` pairs.
+pub type PairMatching<W> = (W, Vec<(usize, usize)>);
+
+/// Algorithm that finds a maximum weight matching of a fixed size.
+pub trait MatchingSolver<W: Weight> {
+    /// Maximum weight matching of size `k` in the `weights` matrix, which has no more
+    /// rows than columns, as its weight and its matched `(row, column)` pairs.
+    ///
+    /// Fails when the matrix has more rows than columns or fewer than `k` rows.
+    fn solve(&self, weights: &[Vec<W>], k: usize) -> Result<PairMatching<W>, MatchingError>;
+
+    /// Maximum weight matching of size at most `k` in the graph of `m` columns whose row
+    /// `i` has the `(column, weight)` edges `rows[i]`; it is smaller than `k` only when
+    /// the graph has no matching of size `k`. Fails when `k` exceeds the number of rows
+    /// or columns.
+    ///
+    /// By default the graph is handed to `solve` as a matrix in which every non-edge
+    /// weighs less than any matching of real edges, and the non-edges are dropped from
+    /// the result.
+    fn solve_sparse(&self, rows: &[Vec<(usize, W)>], m: usize, k: usize) -> Result<PairMatching<W>, MatchingError> {
+        check_sparse(rows, m, k)?;
+        let n = rows.len();
+        let total = rows.iter().flatten().fold(W::zero(), |acc, &(_, w)| acc + w.abs());
+        let forbidden = -(total + total + W::one());
+
+        let mut weights = vec![vec![None; m]; n];
+        for (i, row) in rows.iter().enumerate() {
+            for &(j, w) in row {
+                weights[i][j] = Some(w);
+            }
+        }
+        // `solve` needs at least as many columns as rows.
+        let matched_pairs: Vec<(usize, usize)> = if n <= m {
+            let dense: Vec<Vec<W>> = weights.iter().map(|row| row.iter().map(|w| w.unwrap_or(forbidden)).collect()).collect();
+            self.solve(&dense, k)?.1
+        } else {
+            let dense: Vec<Vec<W>> = (0..m).map(|j| weights.iter().map(|row| row[j].unwrap_or(forbidden)).collect()).collect();
+            self.solve(&dense, k)?.1.into_iter().map(|(j, i)| (i, j)).collect()
+        };
+
+        let matched_pairs: Vec<(usize, usize)> = matched_pairs.into_iter().filter(|&(i, j)| weights[i][j].is_some()).collect();
+        let max_weight = matched_pairs.iter().fold(W::zero(), |acc, &(i, j)| acc + weights[i][j].unwrap_or_else(W::zero));
+        Ok((max_weight, matched_pairs))
+    }
+
+    /// Maximum weight matching of size at most `k` in the graph of `solve_sparse` with
+    /// `units.len()` columns, where column `j` has `units[j]` units and may be matched
+    /// to that many rows. A column is in one matched pair per unit used.
+    ///
+    /// By default every column is copied once per unit and the copies are handed to
+    /// `solve_sparse`.
+    fn solve_b_matching(&self, rows: &[Vec<(usize, W)>], units: &[usize], k: usize) -> Result<PairMatching<W>, MatchingError> {
+        check_b_matching(rows, units, k)?;
+        let mut first_copy = Vec::with_capacity(units.len());
+        let mut copies: Vec<usize> = Vec::new();
+        for (j, &count) in units.iter().enumerate() {
+            first_copy.push(copies.len());
+            copies.extend(std::iter::repeat_n(j, count));
+        }
+        let expanded: Vec<Vec<(usize, W)>> = rows
+            .iter()
+            .map(|row| row.iter().flat_map(|&(j, w)| (first_copy[j]..first_copy[j] + units[j]).map(move |copy| (copy, w))).collect())
+            .collect();
+
+        let (max_weight, matched_pairs) = self.solve_sparse(&expanded, copies.len(), k)?;
+        Ok((max_weight, matched_pairs.into_iter().map(|(i, copy)| (i, copies[copy])).collect()))
+    }
+
+    /// Maximum weight matching of size at most `k` in the graph of `solve_b_matching`
+    /// where row `i` may be matched to up to `capacities[i]` columns, each edge being
+    /// used at most once.
+    ///
+    /// Copying rows as well as columns would let a row use one column twice, so by
+    /// default every edge becomes a gadget instead: a node `x` on the left and a node
+    /// `y` on the right, matched to each other when the edge is not used, or matched to
+    /// a copy of the edge's column and row when it is. Both gadget nodes are worth a
+    /// bonus that outweighs all the edges, so every gadget is matched through in an
+    /// optimal matching of the gadget graph with one pair per edge plus `k`.
+    fn solve_capacitated(&self, rows: &[Vec<(usize, W)>], capacities: &[usize], units: &[usize], k: usize) -> Result<PairMatching<W>, MatchingError> {
+        check_capacitated(rows, capacities, units, k)?;
+        if capacities.iter().take(rows.len()).all(|&capacity| capacity == 1) {
+            return self.solve_b_matching(rows, units, k);
+        }
+
+        let total = rows.iter().flatten().fold(W::zero(), |acc, &(_, w)| acc + w.abs());
+        let bonus = total + total + W::one();
+        let edges: Vec<(usize, usize, W)> = rows.iter().enumerate().flat_map(|(i, row)| row.iter().map(move |&(j, w)| (i, j, w))).collect();
+
+        let offsets = |counts: &[usize]| -> Vec<usize> {
+            counts.iter().scan(0, |next, &count| {
+                let first = *next;
+                *next += count;
+                Some(first)
+            }).collect()
+        };
+        let (row_copy, column_copy) = (offsets(&capacities[..rows.len()]), offsets(units));
+        let num_row_copies: usize = capacities[..rows.len()].iter().sum();
+        let num_column_copies: usize = units.iter().sum();
+
+        // Left nodes are the row copies followed by one `x` per edge, right nodes the
+        // column copies followed by one `y` per edge.
+        let mut gadget_rows: Vec<Vec<(usize, W)>> = vec![Vec::new(); num_row_copies + edges.len()];
+        for (e, &(i, j, w)) in edges.iter().enumerate() {
+            let y = num_column_copies + e;
+            for row in &mut gadget_rows[row_copy[i]..row_copy[i] + capacities[i]] {
+                row.push((y, w + bonus));
+            }
+            let x = num_row_copies + e;
+            gadget_rows[x].extend((column_copy[j]..column_copy[j] + units[j]).map(|copy| (copy, bonus)));
+            gadget_rows[x].push((y, bonus + bonus));
+        }
+        for row in gadget_rows.iter_mut() {
+            row.sort_by_key(|&(j, _)| j);
+        }
+
+        let (_, matched_pairs) = self.solve_sparse(&gadget_rows, num_column_copies + edges.len(), edges.len() + k)?;
+        let mut row_used = vec![false; edges.len()];
+        let mut column_used = vec![false; edges.len()];
+        for (left, right) in matched_pairs {
+            if left < num_row_copies && right >= num_column_copies {
+                row_used[right - num_column_copies] = true;
+            } else if left >= num_row_copies && right < num_column_copies {
+                column_used[left - num_row_copies] = true;
+            }
+        }
+        let used: Vec<&(usize, usize, W)> = edges.iter().enumerate().filter(|&(e, _)| row_used[e] && column_used[e]).map(|(_, edge)| edge).collect();
+        let max_weight = used.iter().fold(W::zero(), |acc, &&(_, _, w)| acc + w);
+        Ok((max_weight, used.into_iter().map(|&(i, j, _)| (i, j)).collect()))
+    }
+
+    /// Maximum weight matchings of every size from 0 to `k` in the graph of `solve_sparse`,
+    /// stopping at the largest size the graph has.
+    ///
+    /// By default every size is solved on its own.
+    fn solve_curve(&self, rows: &[Vec<(usize, W)>], m: usize, k: usize) -> Result<Vec<PairMatching<W>>, MatchingError> {
+        check_sparse(rows, m, 0)?;
+        let mut curve = vec![(W::zero(), Vec::new())];
+        for size in 1..=k.min(rows.len()).min(m) {
+            let (max_weight, matched_pairs) = self.solve_sparse(rows, m, size)?;
+            if matched_pairs.len() < size {
+                break;
+            }
+            curve.push((max_weight, matched_pairs));
+        }
+        Ok(curve)
+    }
+
+    /// Whether an optimal matching of one size is grown into an optimal matching of the
+    /// next size by a single augmenting path, so round robin may keep a group's matching
+    /// between picks instead of calling `solve` on every pick.
+    fn supports_warm_start(&self) -> bool {
+        false
+    }
+
+    /// Whether preferences that are all zero or one are matched by maximum cardinality
+    /// matching instead, so round robin may keep a group's matching by alternating
+    /// paths.
+    fn supports_binary(&self) -> bool {
+        false
+    }
+}
+
+/// Successive shortest paths with Johnson potentials, see `matching.rs`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SuccessiveShortestPathSolver;
+
+impl<W: Weight> MatchingSolver<W> for SuccessiveShortestPathSolver {
+    fn solve(&self, weights: &[Vec<W>], k: usize) -> Result<PairMatching<W>, MatchingError> {
+        fixed_size_max_weight_matching(weights, k)
+    }
+
+    fn solve_sparse(&self, rows: &[Vec<(usize, W)>], m: usize, k: usize) -> Result<PairMatching<W>, MatchingError> {
+        check_sparse(rows, m, k)?;
+        Ok(sparse_fixed_size_max_weight_matching(rows, m, k))
+    }
+
+    fn solve_b_matching(&self, rows: &[Vec<(usize, W)>], units: &[usize], k: usize) -> Result<PairMatching<W>, MatchingError> {
+        check_b_matching(rows, units, k)?;
+        Ok(sparse_fixed_size_max_weight_b_matching(rows, units, k))
+    }
+
+    fn solve_capacitated(&self, rows: &[Vec<(usize, W)>], capacities: &[usize], units: &[usize], k: usize) -> Result<PairMatching<W>, MatchingError> {
+        check_capacitated(rows, capacities, units, k)?;
+        Ok(sparse_fixed_size_max_weight_capacitated_matching(rows, capacities, units, k))
+    }
+
+    fn solve_curve(&self, rows: &[Vec<(usize, W)>], m: usize, k: usize) -> Result<Vec<PairMatching<W>>, MatchingError> {
+        check_sparse(rows, m, 0)?;
+        Ok(sparse_max_weight_matching_curve(rows, m, k))
+    }
+
+    fn supports_warm_start(&self) -> bool {
+        true
+    }
+}
+
+/// Kuhn-Munkres algorithm of the `pathfinding` crate.
+///
+/// Kuhn-Munkres matches every row, so a matching of size `k` of an `n x m` matrix is
+/// found on an `(n + m - k)`-square matrix: `m - k` extra rows and `n - k` extra
+/// columns stand for leaving a column or a row unmatched at no cost, and the extra rows
+/// may not be matched to the extra columns.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct KuhnMunkresSolver;
+
+impl<W: Weight + Bounded> MatchingSolver<W> for KuhnMunkresSolver {
+    fn solve(&self, weights: &[Vec<W>], k: usize) -> Result<PairMatching<W>, MatchingError> {
+        let (n, m) = check_dense(weights, k)?;
+
+        // Any matching using a forbidden entry weighs less than every feasible one.
+        let total = weights.iter().flatten().fold(W::zero(), |acc, w| acc + w.abs());
+        let forbidden = -(total + total + W::one());
+
+        let size = n + m - k;
+        let padded = Matrix::from_fn(size, size, |(row, column)| {
+            Ordered(match (row < n, column < m) {
+                (true, true) => weights[row][column],
+                (false, false) => forbidden,
+                _ => W::zero(),
+            })
+        });
+        let (_, columns) = kuhn_munkres(&padded);
+
+        let matched_pairs: Vec<(usize, usize)> =
+            columns.into_iter().take(n).enumerate().filter(|&(_, column)| column < m).collect();
+        let max_weight = matched_pairs.iter().fold(W::zero(), |acc, &(i, j)| acc + weights[i][j]);
+        Ok((max_weight, matched_pairs))
+    }
+}
+
+/// Bertsekas' auction algorithm for asymmetric assignment with epsilon-scaling, see
+/// `auction.rs`.
+///
+/// Exact on integer weights and within `Weight::epsilon` of the optimum on floating
+/// point ones. Sparse instances are densified by the default methods.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AuctionSolver;
+
+impl<W: Weight> MatchingSolver<W> for AuctionSolver {
+    fn solve(&self, weights: &[Vec<W>], k: usize) -> Result<PairMatching<W>, MatchingError> {
+        check_dense(weights, k)?;
+        auction_fixed_size_max_weight_matching(weights, k)
+    }
+}
+
+/// Hopcroft-Karp on preferences that are all zero or one, see `binary.rs`, and
+/// successive shortest paths on any others.
+///
+/// A matching of such preferences weighs as many pairs of value one as fit, so it
+/// needs no weighted solver; round robin keeps a group's matching by alternating
+/// paths then.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BinarySolver;
+
+impl<W: Weight> MatchingSolver<W> for BinarySolver {
+    fn solve(&self, weights: &[Vec<W>], k: usize) -> Result<PairMatching<W>, MatchingError> {
+        let (_, m) = check_dense(weights, k)?;
+        let rows: Vec<Vec<(usize, W)>> = weights.iter().map(|row| row.iter().copied().enumerate().collect()).collect();
+        if is_binary(&rows, m) {
+            Ok(binary_fixed_size_max_weight_matching(&rows, m, k))
+        } else {
+            SuccessiveShortestPathSolver.solve(weights, k)
+        }
+    }
+
+    fn solve_sparse(&self, rows: &[Vec<(usize, W)>], m: usize, k: usize) -> Result<PairMatching<W>, MatchingError> {
+        check_sparse(rows, m, k)?;
+        if is_binary(rows, m) {
+            Ok(binary_fixed_size_max_weight_matching(rows, m, k))
+        } else {
+            Ok(sparse_fixed_size_max_weight_matching(rows, m, k))
+        }
+    }
+
+    fn solve_b_matching(&self, rows: &[Vec<(usize, W)>], units: &[usize], k: usize) -> Result<PairMatching<W>, MatchingError> {
+        SuccessiveShortestPathSolver.solve_b_matching(rows, units, k)
+    }
+
+    fn solve_capacitated(&self, rows: &[Vec<(usize, W)>], capacities: &[usize], units: &[usize], k: usize) -> Result<PairMatching<W>, MatchingError> {
+        SuccessiveShortestPathSolver.solve_capacitated(rows, capacities, units, k)
+    }
+
+    fn solve_curve(&self, rows: &[Vec<(usize, W)>], m: usize, k: usize) -> Result<Vec<PairMatching<W>>, MatchingError> {
+        SuccessiveShortestPathSolver.solve_curve(rows, m, k)
+    }
+
+    fn supports_warm_start(&self) -> bool {
+        true
+    }
+
+    fn supports_binary(&self) -> bool {
+        true
+    }
+}
+
+/// Solver picked on the command line of the experiment binary.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Solver {
+    #[default]
+    SuccessiveShortestPaths,
+    KuhnMunkres,
+    Auction,
+    Binary,
+}
+
+impl FromStr for Solver {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ssp" => Ok(Solver::SuccessiveShortestPaths),
+            "kuhn-munkres" | "km" => Ok(Solver::KuhnMunkres),
+            "auction" => Ok(Solver::Auction),
+            "binary" | "hopcroft-karp" | "hk" => Ok(Solver::Binary),
+            _ => Err("invalid value for solver"),
+        }
+    }
+}
+
+impl<W: Weight + Bounded> MatchingSolver<W> for Solver {
+    fn solve(&self, weights: &[Vec<W>], k: usize) -> Result<PairMatching<W>, MatchingError> {
+        match self {
+            Solver::SuccessiveShortestPaths => SuccessiveShortestPathSolver.solve(weights, k),
+            Solver::KuhnMunkres => KuhnMunkresSolver.solve(weights, k),
+            Solver::Auction => AuctionSolver.solve(weights, k),
+            Solver::Binary => BinarySolver.solve(weights, k),
+        }
+    }
+
+    fn solve_sparse(&self, rows: &[Vec<(usize, W)>], m: usize, k: usize) -> Result<PairMatching<W>, MatchingError> {
+        match self {
+            Solver::SuccessiveShortestPaths => SuccessiveShortestPathSolver.solve_sparse(rows, m, k),
+            Solver::KuhnMunkres => KuhnMunkresSolver.solve_sparse(rows, m, k),
+            Solver::Auction => AuctionSolver.solve_sparse(rows, m, k),
+            Solver::Binary => BinarySolver.solve_sparse(rows, m, k),
+        }
+    }
+
+    fn solve_b_matching(&self, rows: &[Vec<(usize, W)>], units: &[usize], k: usize) -> Result<PairMatching<W>, MatchingError> {
+        match self {
+            Solver::SuccessiveShortestPaths => SuccessiveShortestPathSolver.solve_b_matching(rows, units, k),
+            Solver::KuhnMunkres => KuhnMunkresSolver.solve_b_matching(rows, units, k),
+            Solver::Auction => AuctionSolver.solve_b_matching(rows, units, k),
+            Solver::Binary => BinarySolver.solve_b_matching(rows, units, k),
+        }
+    }
+
+    fn solve_capacitated(&self, rows: &[Vec<(usize, W)>], capacities: &[usize], units: &[usize], k: usize) -> Result<PairMatching<W>, MatchingError> {
+        match self {
+            Solver::SuccessiveShortestPaths => SuccessiveShortestPathSolver.solve_capacitated(rows, capacities, units, k),
+            Solver::KuhnMunkres => KuhnMunkresSolver.solve_capacitated(rows, capacities, units, k),
+            Solver::Auction => AuctionSolver.solve_capacitated(rows, capacities, units, k),
+            Solver::Binary => BinarySolver.solve_capacitated(rows, capacities, units, k),
+        }
+    }
+
+    fn solve_curve(&self, rows: &[Vec<(usize, W)>], m: usize, k: usize) -> Result<Vec<PairMatching<W>>, MatchingError> {
+        match self {
+            Solver::SuccessiveShortestPaths => SuccessiveShortestPathSolver.solve_curve(rows, m, k),
+            Solver::KuhnMunkres => KuhnMunkresSolver.solve_curve(rows, m, k),
+            Solver::Auction => AuctionSolver.solve_curve(rows, m, k),
+            Solver::Binary => BinarySolver.solve_curve(rows, m, k),
+        }
+    }
+
+    fn supports_warm_start(&self) -> bool {
+        match self {
+            Solver::SuccessiveShortestPaths => MatchingSolver::<W>::supports_warm_start(&SuccessiveShortestPathSolver),
+            Solver::KuhnMunkres => MatchingSolver::<W>::supports_warm_start(&KuhnMunkresSolver),
+            Solver::Auction => MatchingSolver::<W>::supports_warm_start(&AuctionSolver),
+            Solver::Binary => MatchingSolver::<W>::supports_warm_start(&BinarySolver),
+        }
+    }
+
+    fn supports_binary(&self) -> bool {
+        match self {
+            Solver::SuccessiveShortestPaths => MatchingSolver::<W>::supports_binary(&SuccessiveShortestPathSolver),
+            Solver::KuhnMunkres => MatchingSolver::<W>::supports_binary(&KuhnMunkresSolver),
+            Solver::Auction => MatchingSolver::<W>::supports_binary(&AuctionSolver),
+            Solver::Binary => MatchingSolver::<W>::supports_binary(&BinarySolver),
+        }
+    }
+}
+
+/// Weight with the total order `kuhn_munkres` requires; floats are compared with
+/// their tolerance and never hold NaN here.
+#[derive(Clone, Copy, Debug)]
+struct Ordered<W>(W);
+
+impl<W: Weight> PartialEq for Ordered<W> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<W: Weight> Eq for Ordered<W> {}
+
+impl<W: Weight> PartialOrd for Ordered<W> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<W: Weight> Ord for Ordered<W> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+macro_rules! forward_binary_op {
+    ($($trait:ident $method:ident),*) => {
+        $(
+            impl<W: Weight> $trait for Ordered<W> {
+                type Output = Self;
+
+                fn $method(self, other: Self) -> Self {
+                    Ordered(self.0.$method(other.0))
+                }
+            }
+        )*
+    };
+}
+
+forward_binary_op!(Add add, Sub sub, Mul mul, Div div, Rem rem);
+
+impl<W: Weight> Neg for Ordered<W> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Ordered(-self.0)
+    }
+}
+
+impl<W: Weight> Zero for Ordered<W> {
+    fn zero() -> Self {
+        Ordered(W::zero())
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0.is_zero()
+    }
+}
+
+impl<W: Weight> One for Ordered<W> {
+    fn one() -> Self {
+        Ordered(W::one())
+    }
+}
+
+impl<W: Weight> Num for Ordered<W> {
+    type FromStrRadixErr = W::FromStrRadixErr;
+
+    fn from_str_radix(s: &str, radix: u32) -> Result<Self, Self::FromStrRadixErr> {
+        W::from_str_radix(s, radix).map(Ordered)
+    }
+}
+
+impl<W: Weight> Signed for Ordered<W> {
+    fn abs(&self) -> Self {
+        Ordered(self.0.abs())
+    }
+
+    fn abs_sub(&self, other: &Self) -> Self {
+        Ordered(Signed::abs_sub(&self.0, &other.0))
+    }
+
+    fn signum(&self) -> Self {
+        Ordered(self.0.signum())
+    }
+
+    fn is_positive(&self) -> bool {
+        self.0.is_positive()
+    }
+
+    fn is_negative(&self) -> bool {
+        self.0.is_negative()
+    }
+}
+
+impl<W: Weight + Bounded> Bounded for Ordered<W> {
+    fn min_value() -> Self {
+        Ordered(W::min_value())
+    }
+
+    fn max_value() -> Self {
+        Ordered(W::max_value())
+    }
+}
+
+impl<W: Weight> Sum for Ordered<W> {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Ordered::zero(), |acc, w| acc + w)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kuhn_munkres_solver() {
+        let weights = vec![
+            vec![10, 2, 3],
+            vec![4, 15, 6]
+        ];
+        assert_eq!(KuhnMunkresSolver.solve(&weights, 2), Ok((25, vec![(0, 0), (1, 1)])));
+        assert_eq!(KuhnMunkresSolver.solve(&weights, 1), Ok((15, vec![(1, 1)])));
+        assert_eq!(KuhnMunkresSolver.solve(&weights, 0), Ok((0, vec![])));
+    }
+
+    #[test]
+    fn test_solver_errors() {
+        let weights = vec![vec![10, 2], vec![4, 15], vec![1, 1]];
+        for solver in [Solver::SuccessiveShortestPaths, Solver::KuhnMunkres, Solver::Binary] {
+            assert_eq!(solver.solve(&weights, 2), Err(MatchingError::TooManyRows { rows: 3, columns: 2 }));
+            assert_eq!(solver.solve(&weights[..2], 3), Err(MatchingError::InfeasibleSize { requested: 3, largest: 2 }));
+            assert_eq!(solver.solve(&[vec![1, 2], vec![3]], 1), Err(MatchingError::RaggedPreferences { agent: 1, len: 1, expected: 2 }));
+
+            let rows = vec![vec![(0, 1)], vec![(5, 2)]];
+            assert_eq!(solver.solve_sparse(&rows, 3, 1), Err(MatchingError::ItemOutOfRange { agent: 1, item: 5, num_items: 3 }));
+            let rows = vec![vec![(0, 1)], vec![(2, 2)]];
+            assert_eq!(solver.solve_sparse(&rows, 3, 3), Err(MatchingError::InfeasibleSize { requested: 3, largest: 2 }));
+            assert_eq!(solver.solve_curve(&rows, 3, 3).map(|curve| curve.len()), Ok(3));
+        }
+        let weights = vec![vec![1.0, f64::NAN]];
+        assert_eq!(KuhnMunkresSolver.solve(&weights, 1), Err(MatchingError::NotANumber { agent: 0, item: 1 }));
+    }
+
+    #[test]
+    fn test_solvers_agree() {
+        use rand::Rng;
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..50 {
+            let n = rng.gen_range(1..7);
+            let m = rng.gen_range(n..10);
+            let weights: Vec<Vec<i64>> = (0..n).map(|_| (0..m).map(|_| rng.gen_range(-5..20)).collect()).collect();
+            for k in 0..=n {
+                let (max_weight, matched) = KuhnMunkresSolver.solve(&weights, k).unwrap();
+                assert_eq!(max_weight, SuccessiveShortestPathSolver.solve(&weights, k).unwrap().0);
+                assert_eq!(max_weight, AuctionSolver.solve(&weights, k).unwrap().0);
+                assert_eq!(matched.len(), k);
+            }
+
+            let weights: Vec<Vec<f64>> = (0..n).map(|_| (0..m).map(|_| rng.gen()).collect()).collect();
+            for k in 0..=n {
+                let (max_weight, _) = Solver::KuhnMunkres.solve(&weights, k).unwrap();
+                assert!((max_weight - Solver::SuccessiveShortestPaths.solve(&weights, k).unwrap().0).abs() < 1e-9);
+                assert!((max_weight - Solver::Auction.solve(&weights, k).unwrap().0).abs() < 1e-9);
+            }
+
+            // Every solver runs on binary weights, not only the binary one.
+            let weights: Vec<Vec<i64>> = (0..n).map(|_| (0..m).map(|_| rng.gen_range(0..2)).collect()).collect();
+            for k in 0..=n {
+                let (max_weight, matched) = BinarySolver.solve(&weights, k).unwrap();
+                assert_eq!(matched.iter().map(|&(i, j)| weights[i][j]).sum::<i64>(), max_weight);
+                assert_eq!(matched.len(), k);
+                for solver in [Solver::SuccessiveShortestPaths, Solver::KuhnMunkres, Solver::Auction] {
+                    assert_eq!(solver.solve(&weights, k).unwrap().0, max_weight);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_solver_from_str() {
+        assert_eq!("ssp".parse(), Ok(Solver::SuccessiveShortestPaths));
+        assert_eq!("km".parse(), Ok(Solver::KuhnMunkres));
+        assert_eq!("auction".parse(), Ok(Solver::Auction));
+        assert_eq!("binary".parse(), Ok(Solver::Binary));
+        assert!("simplex".parse::<Solver>().is_err());
+    }
+}
@@ -0,0 +1,87 @@
+use crate::error::MatchingError;
+use crate::preferences::Preferences;
+use crate::weight::Weight;
+
+
+/// Which of several maximum weight matchings to return.
+///
+/// Every policy but `Solver` orders the items by a key and prefers the matchings
+/// whose items come first in that order, so the result no longer depends on the
+/// order in which the solver happens to explore the graph.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum TieBreak {
+    /// Whichever optimal matching the solver finds.
+    #[default]
+    Solver,
+    /// Smallest item ids first.
+    SmallestItems,
+    /// Items fewer agents want first, with `demand[item]` agents wanting `item`, e.g.
+    /// as counted by `global_demand`; items beyond `demand` come last. Ties go to the
+    /// smallest id.
+    LowestDemand(Vec<usize>),
+    /// Items in a random order drawn from the seed. The order of two items does not
+    /// depend on which other items there are.
+    Random(u64),
+}
+
+impl TieBreak {
+    /// Key of `item`, smallest first, or `None` when the solver decides.
+    pub fn key(&self, item: usize) -> Option<(u64, usize)> {
+        match self {
+            TieBreak::Solver => None,
+            TieBreak::SmallestItems => Some((0, item)),
+            TieBreak::LowestDemand(demand) => Some((demand.get(item).map_or(u64::MAX, |&demand| demand as u64), item)),
+            TieBreak::Random(seed) => Some((mix(*seed, item), item)),
+        }
+    }
+}
+
+// SplitMix64 finalizer of the seed and the item id
+pub(crate) fn mix(seed: u64, item: usize) -> u64 {
+    let mut z = seed.wrapping_add((item as u64).wrapping_add(1).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Number of agents of `agents` who value each of the items `0..num_items`
+/// positively, for `TieBreak::LowestDemand`.
+pub fn global_demand<W: Weight, P: Preferences<W> + ?Sized>(agents: &[usize], num_items: usize, preferences: &P) -> Result<Vec<usize>, MatchingError> {
+    let items: Vec<usize> = (0..num_items).collect();
+    preferences.validate(agents, &items)?;
+    Ok(items
+        .iter()
+        .map(|&item| agents.iter().filter(|&&agent| preferences.value(agent, item).is_some_and(|value| W::zero() < value)).count())
+        .collect())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tie_break_keys() {
+        assert_eq!(TieBreak::Solver.key(3), None);
+        assert!(TieBreak::SmallestItems.key(2) < TieBreak::SmallestItems.key(3));
+
+        let preferences: Vec<Vec<i64>> = vec![vec![1, 0, 2], vec![3, -1, 0]];
+        let demand = global_demand(&[0, 1], 3, &preferences).unwrap();
+        assert_eq!(demand, vec![2, 0, 1]);
+        let lowest = TieBreak::LowestDemand(demand);
+        let mut items = vec![0, 1, 2, 3];
+        items.sort_by_key(|&item| lowest.key(item));
+        assert_eq!(items, vec![1, 2, 0, 3]);
+
+        // The random order is reproducible and does not depend on the other items.
+        let random = TieBreak::Random(7);
+        assert_eq!(random.key(5), TieBreak::Random(7).key(5));
+        assert_ne!(random.key(5), TieBreak::Random(8).key(5));
+        let mut few: Vec<usize> = (0..5).collect();
+        let mut many: Vec<usize> = (0..50).collect();
+        few.sort_by_key(|&item| random.key(item));
+        many.sort_by_key(|&item| random.key(item));
+        many.retain(|&item| item < 5);
+        assert_eq!(few, many);
+    }
+}
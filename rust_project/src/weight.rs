@@ -0,0 +1,146 @@
+use num_integer::Integer;
+use num_rational::Ratio;
+use num_traits::{CheckedAdd, CheckedMul, Num, Signed, ToPrimitive};
+use std::cmp::Ordering;
+use std::fmt::Debug;
+
+/// Numeric type of preferences and matching weights.
+///
+/// Integers and rationals are compared exactly. Floating point weights carry an
+/// explicit `epsilon`: two weights closer than that are treated as equal, so that
+/// rounding noise neither creates spurious improvements nor breaks ties at random.
+pub trait Weight: Copy + Debug + PartialOrd + Num + Signed {
+    /// Largest difference that is still treated as equality.
+    fn epsilon() -> Self {
+        Self::zero()
+    }
+
+    /// Whether the value is a floating point NaN, which no matching can be built on.
+    fn is_nan(self) -> bool {
+        false
+    }
+
+    /// `self < other` beyond the tolerance.
+    fn definitely_lt(self, other: Self) -> bool {
+        self + Self::epsilon() < other
+    }
+
+    /// Total order used where the heap and sorting need one.
+    fn total_cmp(&self, other: &Self) -> Ordering {
+        self.partial_cmp(other).unwrap_or(Ordering::Equal)
+    }
+
+    /// `self + other`, or `None` when an exact type overflows.
+    fn checked_add(self, other: Self) -> Option<Self> {
+        Some(self + other)
+    }
+
+    /// `self * other`, or `None` when an exact type overflows.
+    fn checked_mul(self, other: Self) -> Option<Self> {
+        Some(self * other)
+    }
+
+    /// Nearest `f64`, for the entitlements of a weighted picking sequence.
+    fn as_f64(self) -> f64;
+}
+
+macro_rules! integer_weight {
+    ($($t:ty),*) => {
+        $(
+            impl Weight for $t {
+                fn as_f64(self) -> f64 {
+                    self as f64
+                }
+
+                fn checked_add(self, other: $t) -> Option<$t> {
+                    <$t>::checked_add(self, other)
+                }
+
+                fn checked_mul(self, other: $t) -> Option<$t> {
+                    <$t>::checked_mul(self, other)
+                }
+            }
+        )*
+    };
+}
+
+integer_weight!(i32, i64, i128, isize);
+
+impl Weight for f32 {
+    fn epsilon() -> f32 {
+        1e-6
+    }
+
+    fn is_nan(self) -> bool {
+        f32::is_nan(self)
+    }
+
+    fn as_f64(self) -> f64 {
+        self as f64
+    }
+}
+
+impl Weight for f64 {
+    fn epsilon() -> f64 {
+        1e-9
+    }
+
+    fn is_nan(self) -> bool {
+        f64::is_nan(self)
+    }
+
+    fn as_f64(self) -> f64 {
+        self
+    }
+}
+
+impl<T> Weight for Ratio<T>
+where
+    T: Copy + Debug + Integer + Signed + CheckedAdd + CheckedMul + ToPrimitive,
+{
+    fn as_f64(self) -> f64 {
+        match (self.numer().to_f64(), self.denom().to_f64()) {
+            (Some(numer), Some(denom)) => numer / denom,
+            _ => f64::NAN,
+        }
+    }
+
+    fn checked_add(self, other: Self) -> Option<Self> {
+        CheckedAdd::checked_add(&self, &other)
+    }
+
+    fn checked_mul(self, other: Self) -> Option<Self> {
+        CheckedMul::checked_mul(&self, &other)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_epsilon() {
+        assert!(!(0.1 + 0.2).definitely_lt(0.3));
+        assert!(!0.3.definitely_lt(0.1 + 0.2));
+        assert!(0.3.definitely_lt(0.3 + 1e-6));
+        assert!(1.definitely_lt(2));
+        assert!(!Ratio::new(1, 3).definitely_lt(Ratio::new(2, 6)));
+        assert!(Ratio::new(1, 3).definitely_lt(Ratio::new(1, 2)));
+    }
+
+    #[test]
+    fn test_checked_arithmetic() {
+        assert_eq!(Weight::checked_mul(i64::MAX / 2, 3), None);
+        assert_eq!(Weight::checked_add(2i32, 3), Some(5));
+        assert_eq!(Weight::checked_mul(Ratio::new(i64::MAX, 2), Ratio::from_integer(4)), None);
+        assert_eq!(Weight::checked_mul(1e300, 1e300), Some(f64::INFINITY));
+    }
+
+    #[test]
+    fn test_as_f64() {
+        assert_eq!(3i64.as_f64(), 3.0);
+        assert_eq!(Ratio::new(3i64, 4).as_f64(), 0.75);
+        assert_eq!(0.5f32.as_f64(), 0.5);
+    }
+}
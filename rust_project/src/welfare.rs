@@ -0,0 +1,271 @@
+use crate::allocation::{bundle_value, round_robin_allocation_with_quotas};
+use crate::error::MatchingError;
+use crate::matching::compute_max_weight_matching_with;
+use crate::picking::PickingSequence;
+use crate::preferences::Preferences;
+use crate::solver::{MatchingSolver, SuccessiveShortestPathSolver};
+use crate::tie_break::TieBreak;
+use crate::weight::Weight;
+use std::collections::HashSet;
+
+
+/// Bundle of every group, Nash product and utility of every group.
+pub type NashOutcome<W> = (Vec<HashSet<usize>>, W, Vec<W>);
+
+/// Nash welfare compared as usual when some utilities are not positive: first the
+/// number of groups of positive utility, then the product of their utilities.
+#[derive(Clone, Copy, Debug)]
+struct Welfare<W> {
+    positive: usize,
+    product: W,
+}
+
+impl<W: Weight> Welfare<W> {
+    fn of(utilities: &[W]) -> Self {
+        utilities.iter().filter(|&&utility| W::zero() < utility).fold(Welfare { positive: 0, product: W::one() }, |welfare, &utility| Welfare {
+            positive: welfare.positive + 1,
+            product: welfare.product * utility,
+        })
+    }
+
+    fn definitely_lt(&self, other: &Self) -> bool {
+        self.positive < other.positive || (self.positive == other.positive && self.product.definitely_lt(other.product))
+    }
+}
+
+// Checks that every member of the groups values every item, none of them negatively
+fn check_goods<W: Weight, P: Preferences<W> + ?Sized>(num_items: usize, quotas: &[usize], groups: &[Vec<usize>], preferences: &P) -> Result<(), MatchingError> {
+    if groups.len() < quotas.len() {
+        return Err(MatchingError::MissingGroups { num_groups: quotas.len(), found: groups.len() });
+    }
+    let items: Vec<usize> = (0..num_items).collect();
+    for group in groups.iter().take(quotas.len()) {
+        preferences.validate(group, &items)?;
+        for &agent in group {
+            if let Some(item) = items.iter().copied().find(|&item| preferences.value(agent, item).is_some_and(|value| value < W::zero())) {
+                return Err(MatchingError::NegativeValuation { agent, item });
+            }
+        }
+    }
+    Ok(())
+}
+
+fn nash_product<W: Weight>(utilities: &[W]) -> W {
+    utilities.iter().fold(W::one(), |product, &utility| product * utility)
+}
+
+fn outcome<W: Weight>(bundles: Vec<Vec<usize>>, utilities: Vec<W>) -> NashOutcome<W> {
+    let product = nash_product(&utilities);
+    (bundles.into_iter().map(|bundle| bundle.into_iter().collect()).collect(), product, utilities)
+}
+
+pub fn max_nash_welfare_allocation<W: Weight, P: Preferences<W> + ?Sized>(num_items: usize, quotas: &[usize], groups: &[Vec<usize>], preferences: &P) -> Result<NashOutcome<W>, MatchingError> {
+    max_nash_welfare_allocation_with(&SuccessiveShortestPathSolver, num_items, quotas, groups, preferences)
+}
+
+/// Allocation of maximum Nash welfare, where group `p` takes up to `quotas[p]`
+/// items and values a bundle at its maximum weight matching of
+/// `min(group size, bundle size)` pairs.
+///
+/// Branch and bound over the group, or none, of every item in turn, starting from
+/// `nash_welfare_local_search_with`. A branch is cut when even every remaining item
+/// added to every bundle, as far as the group and its quota can use them, does not
+/// beat the best allocation so far. Takes up to `(quotas.len() + 1)^num_items`
+/// steps, so only for small instances. Fails when fewer than `quotas.len()` groups
+/// are given, when `preferences` does not cover every member and item, or when a
+/// preference is negative: the bound only holds for goods.
+pub fn max_nash_welfare_allocation_with<W: Weight, P: Preferences<W> + ?Sized, S: MatchingSolver<W>>(solver: &S, num_items: usize, quotas: &[usize], groups: &[Vec<usize>], preferences: &P) -> Result<NashOutcome<W>, MatchingError> {
+    let (start, _, utilities) = nash_welfare_local_search_with(solver, num_items, quotas, groups, preferences)?;
+    let mut best_bundles: Vec<Vec<usize>> = start.into_iter().map(|bundle| bundle.into_iter().collect()).collect();
+    let mut best_utilities = utilities;
+    let mut bundles: Vec<Vec<usize>> = vec![Vec::new(); quotas.len()];
+    branch(solver, 0, num_items, quotas, groups, preferences, &mut bundles, &mut best_bundles, &mut best_utilities)?;
+    Ok(outcome(best_bundles, best_utilities))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn branch<W: Weight, P: Preferences<W> + ?Sized, S: MatchingSolver<W>>(solver: &S, item: usize, num_items: usize, quotas: &[usize], groups: &[Vec<usize>], preferences: &P, bundles: &mut Vec<Vec<usize>>, best_bundles: &mut Vec<Vec<usize>>, best_utilities: &mut Vec<W>) -> Result<(), MatchingError> {
+    // Every group with all the remaining items, as many as it can use.
+    let mut bounds = Vec::new();
+    for (p, group) in groups.iter().take(quotas.len()).enumerate() {
+        let mut reachable = bundles[p].clone();
+        reachable.extend(item..num_items);
+        let size = group.len().min(quotas[p]).min(reachable.len());
+        bounds.push(compute_max_weight_matching_with(solver, group.clone(), reachable, preferences, size)?.0);
+    }
+    if !Welfare::of(best_utilities).definitely_lt(&Welfare::of(&bounds)) {
+        return Ok(());
+    }
+    if item == num_items {
+        // The bound of a complete allocation is its own welfare.
+        *best_bundles = bundles.clone();
+        *best_utilities = bounds;
+        return Ok(());
+    }
+    for p in 0..quotas.len() {
+        if bundles[p].len() < quotas[p] {
+            bundles[p].push(item);
+            branch(solver, item + 1, num_items, quotas, groups, preferences, bundles, best_bundles, best_utilities)?;
+            bundles[p].pop();
+        }
+    }
+    branch(solver, item + 1, num_items, quotas, groups, preferences, bundles, best_bundles, best_utilities)
+}
+
+pub fn nash_welfare_local_search<W: Weight, P: Preferences<W> + ?Sized>(num_items: usize, quotas: &[usize], groups: &[Vec<usize>], preferences: &P) -> Result<NashOutcome<W>, MatchingError> {
+    nash_welfare_local_search_with(&SuccessiveShortestPathSolver, num_items, quotas, groups, preferences)
+}
+
+/// Allocation of locally maximum Nash welfare, with the inputs and valuations of
+/// `max_nash_welfare_allocation_with`, for instances too large for it.
+///
+/// Starts from round robin and moves an item to another group, or from nobody to a
+/// group, or swaps two items between groups, as long as the Nash welfare increases;
+/// no single such change improves the result. Fails as `max_nash_welfare_allocation_with`.
+pub fn nash_welfare_local_search_with<W: Weight, P: Preferences<W> + ?Sized, S: MatchingSolver<W>>(solver: &S, num_items: usize, quotas: &[usize], groups: &[Vec<usize>], preferences: &P) -> Result<NashOutcome<W>, MatchingError> {
+    check_goods(num_items, quotas, groups, preferences)?;
+    let num_groups = quotas.len();
+    let (start, mut utilities, _) = round_robin_allocation_with_quotas(solver, &TieBreak::SmallestItems, &PickingSequence::RoundRobin, num_items, quotas, groups, preferences)?;
+    let mut bundles: Vec<Vec<usize>> = start
+        .into_iter()
+        .map(|bundle| {
+            let mut bundle: Vec<usize> = bundle.into_iter().collect();
+            bundle.sort_unstable();
+            bundle
+        })
+        .collect();
+    let value = |p: usize, bundle: &[usize]| bundle_value(solver, &groups[p], bundle, preferences);
+
+    let mut improved = true;
+    while improved {
+        improved = false;
+        let mut owner: Vec<Option<usize>> = vec![None; num_items];
+        for (p, bundle) in bundles.iter().enumerate() {
+            bundle.iter().for_each(|&item| owner[item] = Some(p));
+        }
+        let current = Welfare::of(&utilities);
+
+        // Moves of one item to a group below its quota.
+        'moves: for (item, &holder) in owner.iter().enumerate() {
+            for q in (0..num_groups).filter(|&q| holder != Some(q) && bundles[q].len() < quotas[q]) {
+                let mut candidate = utilities.clone();
+                let mut gaining = bundles[q].clone();
+                gaining.push(item);
+                candidate[q] = value(q, &gaining)?;
+                let losing = match holder {
+                    Some(p) => {
+                        let losing: Vec<usize> = bundles[p].iter().copied().filter(|&x| x != item).collect();
+                        candidate[p] = value(p, &losing)?;
+                        Some((p, losing))
+                    }
+                    None => None,
+                };
+                if current.definitely_lt(&Welfare::of(&candidate)) {
+                    bundles[q] = gaining;
+                    if let Some((p, losing)) = losing {
+                        bundles[p] = losing;
+                    }
+                    utilities = candidate;
+                    improved = true;
+                    break 'moves;
+                }
+            }
+        }
+        if improved {
+            continue;
+        }
+
+        // Swaps of two items of different groups.
+        'swaps: for first in 0..num_items {
+            for second in first + 1..num_items {
+                let (Some(p), Some(q)) = (owner[first], owner[second]) else {
+                    continue;
+                };
+                if p == q {
+                    continue;
+                }
+                let swapped = |bundle: &[usize], out: usize, into: usize| bundle.iter().map(|&x| if x == out { into } else { x }).collect::<Vec<usize>>();
+                let (for_p, for_q) = (swapped(&bundles[p], first, second), swapped(&bundles[q], second, first));
+                let mut candidate = utilities.clone();
+                candidate[p] = value(p, &for_p)?;
+                candidate[q] = value(q, &for_q)?;
+                if current.definitely_lt(&Welfare::of(&candidate)) {
+                    bundles[p] = for_p;
+                    bundles[q] = for_q;
+                    utilities = candidate;
+                    improved = true;
+                    break 'swaps;
+                }
+            }
+        }
+    }
+    Ok(outcome(bundles, utilities))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn test_max_nash_welfare_allocation() {
+        // Round robin gives group 0 item 0, which both value most; MNW gives it to
+        // group 1, which values nothing else.
+        let groups = vec![vec![0], vec![1]];
+        let preferences: Vec<Vec<i64>> = vec![vec![3, 2], vec![1, 0]];
+        let (allocation, product, utilities) = max_nash_welfare_allocation(2, &[1, 1], &groups, &preferences).unwrap();
+        assert_eq!(allocation, vec![HashSet::from([1]), HashSet::from([0])]);
+        assert_eq!((product, utilities), (2, vec![2, 1]));
+
+        // Exhaustive search over every allocation agrees on small instances, and
+        // local search never beats it.
+        let mut rng = rand::thread_rng();
+        for _ in 0..20 {
+            let num_items = rng.gen_range(1..6);
+            let groups = vec![vec![0, 1], vec![2], vec![3]];
+            let quotas = [2, 2, 1];
+            let preferences: Vec<Vec<i64>> = (0..4).map(|_| (0..num_items).map(|_| rng.gen_range(0..6)).collect()).collect();
+
+            let mut best: Option<Welfare<i64>> = None;
+            for code in 0..4usize.pow(num_items as u32) {
+                let mut bundles: Vec<Vec<usize>> = vec![Vec::new(); 3];
+                let mut rest = code;
+                for item in 0..num_items {
+                    if rest % 4 < 3 {
+                        bundles[rest % 4].push(item);
+                    }
+                    rest /= 4;
+                }
+                if (0..3).any(|p| bundles[p].len() > quotas[p]) {
+                    continue;
+                }
+                let utilities: Vec<i64> = (0..3).map(|p| bundle_value(&SuccessiveShortestPathSolver, &groups[p], &bundles[p], &preferences).unwrap()).collect();
+                let welfare = Welfare::of(&utilities);
+                if best.is_none_or(|best| best.definitely_lt(&welfare)) {
+                    best = Some(welfare);
+                }
+            }
+            let best = best.unwrap();
+
+            let (allocation, product, utilities) = max_nash_welfare_allocation(num_items, &quotas, &groups, &preferences).unwrap();
+            let welfare = Welfare::of(&utilities);
+            assert_eq!((welfare.positive, welfare.product), (best.positive, best.product));
+            assert_eq!(product, utilities.iter().product::<i64>());
+            for (p, bundle) in allocation.iter().enumerate() {
+                assert!(bundle.len() <= quotas[p]);
+                let bundle: Vec<usize> = bundle.iter().copied().collect();
+                assert_eq!(bundle_value(&SuccessiveShortestPathSolver, &groups[p], &bundle, &preferences), Ok(utilities[p]));
+            }
+
+            let (_, _, utilities) = nash_welfare_local_search(num_items, &quotas, &groups, &preferences).unwrap();
+            assert!(!best.definitely_lt(&Welfare::of(&utilities)));
+        }
+
+        // A chore breaks the bound, so it is rejected rather than misjudged.
+        let preferences: Vec<Vec<i64>> = vec![vec![3, 2], vec![1, -1]];
+        assert_eq!(max_nash_welfare_allocation(2, &[1, 1], &[vec![0], vec![1]], &preferences), Err(MatchingError::NegativeValuation { agent: 1, item: 1 }));
+        assert_eq!(nash_welfare_local_search(2, &[1, 1], &[vec![0], vec![1]], &preferences), Err(MatchingError::NegativeValuation { agent: 1, item: 1 }));
+        assert_eq!(max_nash_welfare_allocation(2, &[1, 1], &[vec![0]], &preferences), Err(MatchingError::MissingGroups { num_groups: 2, found: 1 }));
+    }
+}